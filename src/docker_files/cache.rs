@@ -2,6 +2,7 @@
 use std::env;
 use std::path::PathBuf;
 use lazy_static::lazy_static;
+use crate::project_config::config::NanoforgeConfig;
 
 
 /// Processes the image name into a directory name.
@@ -25,7 +26,7 @@ lazy_static! {
 
 
 lazy_static! {
-    // The patient to the cache directory (please put this in your .gitignore file).
+    // The default path to the cache directory (please put this in your .gitignore file).
     pub static ref CACHE_DIR: PathBuf = {
         let mut path = CURRENT_WORKING_DIR.clone();
         path.push(".nanoservices_cache");
@@ -34,60 +35,101 @@ lazy_static! {
 }
 
 
-lazy_static! {
-    // The path to the domain services cache directory.
-    pub static ref CACHE_DOMAIN_SERVICES_DIR: PathBuf = {
-        let mut path = CACHE_DIR.clone();
-        path.push("domain_services");
-        path
-    };
+/// Gets the nanoservices cache directory of a profile.
+///
+/// # Notes
+/// The `dev` profile uses the `domain_services/nanoservices` directory directly so existing caches keep working,
+/// other profiles get their own directory so switching profiles never mixes images.
+///
+/// # Arguments
+/// * `config` - The configuration with the cache directory and the profile to get the cache directory of.
+///
+/// # Returns
+/// The path to the nanoservices cache directory of the profile.
+pub fn nanoservices_cache_dir(config: &NanoforgeConfig) -> PathBuf {
+    match config.profile.is_dev() {
+        true => config.cache_dir.join("domain_services").join("nanoservices"),
+        false => config.cache_dir.join("profiles").join(config.profile.name()).join("nanoservices")
+    }
 }
 
 
-lazy_static! {
-    // The path to the domain services tar cache directory.
-    pub static ref CACHE_DOMAIN_SERVICES_TAR_DIR: PathBuf = {
-        let mut path = CACHE_DIR.clone();
-        path.push("domain_services_tar");
-        path
-    };
+/// Gets the nanoservices tar cache directory of a profile.
+///
+/// # Arguments
+/// * `config` - The configuration with the cache directory and the profile to get the tar cache directory of.
+///
+/// # Returns
+/// The path to the nanoservices tar cache directory of the profile.
+pub fn nanoservices_tar_cache_dir(config: &NanoforgeConfig) -> PathBuf {
+    match config.profile.is_dev() {
+        true => config.cache_dir.join("domain_services_tar").join("nanoservices_tar"),
+        false => config.cache_dir.join("profiles").join(config.profile.name()).join("nanoservices_tar")
+    }
 }
 
 
-lazy_static! {
-    // The path to the nanoservices cache directory.
-    pub static ref CACHE_NANOSERVICES_DIR: PathBuf = {
-        let mut path = CACHE_DOMAIN_SERVICES_DIR.clone();
-        path.push("nanoservices");
-        path
-    };
+/// Gets the directory of the shim crates generated in the non-invasive mode.
+///
+/// # Arguments
+/// * `config` - The configuration with the cache directory.
+///
+/// # Returns
+/// The path to the shims cache directory.
+pub fn shims_cache_dir(config: &NanoforgeConfig) -> PathBuf {
+    config.cache_dir.join("shims")
 }
 
 
-lazy_static! {
-    // The path to the nanoservices tar cache directory.
-    pub static ref CACHE_NANOSERVICES_TAR_DIR: PathBuf = {
-        let mut path = CACHE_DOMAIN_SERVICES_TAR_DIR.clone();
-        path.push("nanoservices_tar");
-        path
-    };
+/// Wipes the nanoservices cache of a profile and creates new cache directories for it.
+///
+/// # Notes
+/// The caches of the other profiles are kept.
+///
+/// # Arguments
+/// * `config` - The configuration with the cache directory and the profile to wipe the cache of.
+///
+/// # Returns
+/// None
+pub fn wipe_and_create_cache(config: &NanoforgeConfig) {
+    for dir in [nanoservices_cache_dir(config), nanoservices_tar_cache_dir(config)] {
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).expect(
+                "Failed to remove cache directory"
+            );
+        }
+    }
+    create_cache(config);
 }
 
 
-/// Wipes the cache directory and creates a new cache directory.
+/// Creates the nanoservices cache directories of a profile if they do not exist.
+///
+/// # Arguments
+/// * `config` - The configuration with the cache directory and the profile to create the cache of.
+///
+/// # Returns
+/// None
+pub fn create_cache(config: &NanoforgeConfig) {
+    for dir in [nanoservices_cache_dir(config), nanoservices_tar_cache_dir(config)] {
+        std::fs::create_dir_all(&dir).expect(
+            "Failed to create nanoservices cache directory"
+        );
+    }
+}
+
+
+/// Removes the cache directory if it exists.
+///
+/// # Arguments
+/// * `config` - The configuration with the cache directory.
 ///
 /// # Returns
 /// None
-pub fn wipe_and_create_cache() {
-    if CACHE_DIR.exists() {
-        std::fs::remove_dir_all(&*CACHE_DIR).expect(
+pub fn remove_cache(config: &NanoforgeConfig) {
+    if config.cache_dir.exists() {
+        std::fs::remove_dir_all(&config.cache_dir).expect(
             "Failed to remove cache directory"
         );
     }
-    std::fs::create_dir_all(&*CACHE_NANOSERVICES_DIR).expect(
-        "Failed to create nanoservices cache directory"
-    );
-    std::fs::create_dir_all(&*CACHE_NANOSERVICES_TAR_DIR).expect(
-        "Failed to create nanoservices tar cache directory"
-    );
 }
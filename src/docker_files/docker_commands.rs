@@ -10,17 +10,19 @@ use nanoservices_utils::{
     }
 };
 use super::cache::process_image_name;
+use crate::project_config::kernel::ImageBackend;
 
 
 /// Pulls a docker image from the docker registry.
 ///
 /// # Arguments
 /// * `image_name` - A string slice that holds the name of the docker image to pull.
+/// * `backend` - The container engine that pulls the image.
 ///
 /// # Returns
 /// None
-pub fn pull_docker_image(image_name: &str) -> Result<(), NanoServiceError> {
-    let status = safe_eject!(Command::new("docker")
+pub fn pull_docker_image(image_name: &str, backend: ImageBackend) -> Result<(), NanoServiceError> {
+    let status = safe_eject!(Command::new(backend.command())
         .args(["pull", image_name])
         .status(),
         NanoServiceErrorStatus::Unknown,
@@ -47,11 +49,12 @@ pub fn pull_docker_image(image_name: &str) -> Result<(), NanoServiceError> {
 /// # Arguments
 /// * `image_name` - The name of the Docker image to pull and unpack.
 /// * `tar_path` - The path to save the unpacked Docker image.
+/// * `backend` - The container engine that pulls and saves the image.
 ///
 /// # Returns
 /// The path to where the compressed Docker image files are stored
-pub fn save_docker_image(image_name: &str, tar_path: &str) -> Result<String, NanoServiceError> {
-    pull_docker_image(image_name)?; // Ensure the image is pulled before saving it
+pub fn save_docker_image(image_name: &str, tar_path: &str, backend: ImageBackend) -> Result<String, NanoServiceError> {
+    pull_docker_image(image_name, backend)?; // Ensure the image is pulled before saving it
 
     let tar_path = std::path::Path::new(tar_path);
     let tar_file = image_name;
@@ -72,7 +75,7 @@ pub fn save_docker_image(image_name: &str, tar_path: &str) -> Result<String, Nan
 
     println!("Tar path: {:?}", tar_path);
 
-    let _ = safe_eject!(Command::new("docker")
+    let _ = safe_eject!(Command::new(backend.command())
         .args(["save", "-o", unpack_tar_path, image_name])
         .status(),
         NanoServiceErrorStatus::Unknown,
@@ -2,31 +2,55 @@
 pub mod unpacking;
 pub mod cache;
 pub mod docker_commands;
-use nanoservices_utils::errors::NanoServiceError;
+pub mod registry;
+use nanoservices_utils::errors::{NanoServiceError, NanoServiceErrorStatus};
 use cache::process_image_name;
+use registry::mirror_image;
+use crate::project_config::config::NanoforgeConfig;
+use crate::project_config::kernel::PullPolicy;
 
 
 /// Downloads a docker image and unpacks it to the nanoservices cache directory.
 ///
+/// # Notes
+/// The image is pulled from the mirror of its registry if there is one, but it is unpacked under its own name so
+/// the paths in the cache do not depend on the mirrors. With the `missing` pull policy an image that is already
+/// unpacked is not pulled again, and with the `never` pull policy (or in the offline mode) nothing is pulled.
+///
 /// # Arguments
 /// * `image` - A string slice that holds the name of the docker image to download.
+/// * `config` - The configuration with the cache, backend, mirrors, and pull policy.
 ///
 /// # Returns
 /// The paths to where the files have been unpacked to from the docker image
-pub fn download_nanoservice(image: &str) -> Result<String, NanoServiceError> {
+pub fn download_nanoservice(image: &str, config: &NanoforgeConfig) -> Result<String, NanoServiceError> {
     let image_file = process_image_name(&image.to_string());
+    let unpack_path = cache::nanoservices_cache_dir(config).join(
+        image_file.as_str()
+    );
+    // unwrap is safe here because we are using a hardcoded path
+    let unpacked = unpack_path.to_str().unwrap().to_string();
+    match config.pull {
+        PullPolicy::Missing if unpack_path.exists() => return Ok(unpacked),
+        PullPolicy::Never if unpack_path.exists() => return Ok(unpacked),
+        PullPolicy::Never => {
+            return Err(NanoServiceError::new(
+                format!("The image {} is not in the cache and pulling images is disabled", image),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        },
+        _ => {}
+    }
+    let pulled_image = mirror_image(image, &config.mirrors);
     let main_path = docker_commands::save_docker_image(
-        image,
+        &pulled_image,
         // unwrap is safe here because we are using a hardcoded path
-        cache::CACHE_NANOSERVICES_TAR_DIR.to_str().unwrap(),
+        cache::nanoservices_tar_cache_dir(config).to_str().unwrap(),
+        config.backend
     )?;
-    let unpack_path = cache::CACHE_NANOSERVICES_DIR.join(
-        image_file.as_str()
-    );
     let final_path = unpacking::extract_layers(
         main_path.as_str(),
-        // unwrap is safe here because we are using a hardcoded path
-        unpack_path.to_str().unwrap(),
+        unpacked.as_str(),
     )?;
     Ok(final_path)
 }
@@ -0,0 +1,490 @@
+//! Defines the listing of the tags of an image in its registry.
+//!
+//! # Notes
+//! The registry APIs are called with `curl` in the same way that the `docker` CLI is called for pulling, so no
+//! HTTP client is needed. Images on Docker Hub are listed with the Docker Hub API as the registry API requires a
+//! token there, images on other registries are listed with the registry API (`/v2/<repository>/tags/list`). The
+//! registry API follows the `Link` header through the pages of tags, and fetches a bearer token from the realm of
+//! the `WWW-Authenticate` challenge when the registry answers `401`, with the credentials that `docker login` stored
+//! in the Docker config file if there are any.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use serde_json::Value;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+
+
+/// The parts of an image reference.
+///
+/// # Fields
+/// * `registry` - The registry of the image (`None` for Docker Hub).
+/// * `repository` - The repository of the image in the registry.
+/// * `tag` - The tag of the image if one is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageReference {
+    pub registry: Option<String>,
+    pub repository: String,
+    pub tag: Option<String>,
+}
+
+
+impl ImageReference {
+
+    /// Parses an image reference such as `registry.example.com:5000/team/nan-one:1.0.0`.
+    ///
+    /// # Arguments
+    /// * `image` - The image reference to parse.
+    ///
+    /// # Returns
+    /// The parts of the image reference.
+    pub fn parse(image: &str) -> ImageReference {
+        // the tag is after the last colon that comes after the last slash (a colon before it is a registry port)
+        let name_start = image.rfind('/').map(|index| index + 1).unwrap_or(0);
+        let (name, tag) = match image[name_start..].rfind(':') {
+            Some(index) => (&image[..name_start + index], Some(image[name_start + index + 1..].to_string())),
+            None => (image, None)
+        };
+        let (registry, repository) = match name.split_once('/') {
+            Some((first, rest)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+                (Some(first.to_string()), rest.to_string())
+            },
+            _ => (None, name.to_string())
+        };
+        ImageReference { registry, repository, tag }
+    }
+
+    /// Gets the image reference without the tag.
+    ///
+    /// # Returns
+    /// The registry and repository of the image.
+    pub fn name(&self) -> String {
+        match &self.registry {
+            Some(registry) => format!("{}/{}", registry, self.repository),
+            None => self.repository.clone()
+        }
+    }
+
+}
+
+
+/// The key of Docker Hub in the registry mirrors.
+pub const DOCKER_HUB: &str = "docker.io";
+
+
+/// Rewrites an image reference to pull it from the mirror of its registry.
+///
+/// # Arguments
+/// * `image` - The image reference to rewrite.
+/// * `mirrors` - The mirrors keyed by the registry (`docker.io` for Docker Hub).
+///
+/// # Returns
+/// The image reference on the mirror, or the image reference untouched if its registry has no mirror.
+pub fn mirror_image(image: &str, mirrors: &HashMap<String, String>) -> String {
+    let reference = ImageReference::parse(image);
+    let mirror = match mirrors.get(reference.registry.as_deref().unwrap_or(DOCKER_HUB)) {
+        Some(mirror) => mirror.trim_end_matches('/'),
+        None => return image.to_string()
+    };
+    // official images live in the `library` namespace on Docker Hub
+    let repository = match reference.registry.is_none() && !reference.repository.contains('/') {
+        true => format!("library/{}", reference.repository),
+        false => reference.repository
+    };
+    match reference.tag {
+        Some(tag) => format!("{}/{}:{}", mirror, repository, tag),
+        None => format!("{}/{}", mirror, repository)
+    }
+}
+
+
+/// Lists all the tags of an image in its registry.
+///
+/// # Arguments
+/// * `image` - The image to list the tags of (the tag of the image is ignored).
+///
+/// # Returns
+/// All the tags of the image.
+pub fn list_image_tags(image: &str) -> Result<Vec<String>, NanoServiceError> {
+    let reference = ImageReference::parse(image);
+    match &reference.registry {
+        Some(registry) => list_registry_tags(registry, &reference.repository),
+        None => {
+            // official images live in the `library` namespace on Docker Hub
+            let repository = match reference.repository.contains('/') {
+                true => reference.repository.clone(),
+                false => format!("library/{}", reference.repository)
+            };
+            let mut url = format!("https://hub.docker.com/v2/repositories/{}/tags?page_size=100", repository);
+            let mut tags = Vec::new();
+            loop {
+                let response = fetch_json(&url)?;
+                for result in response["results"].as_array().into_iter().flatten() {
+                    if let Some(name) = result["name"].as_str() {
+                        tags.push(name.to_string());
+                    }
+                }
+                match response["next"].as_str() {
+                    Some(next) => url = next.to_string(),
+                    None => break
+                }
+            }
+            Ok(tags)
+        }
+    }
+}
+
+
+/// Lists all the tags of a repository with the registry API.
+///
+/// # Arguments
+/// * `registry` - The registry hosting the repository.
+/// * `repository` - The repository to list the tags of.
+///
+/// # Returns
+/// All the tags of the repository, following the `Link` header through the pages.
+fn list_registry_tags(registry: &str, repository: &str) -> Result<Vec<String>, NanoServiceError> {
+    let mut url = format!("https://{}/v2/{}/tags/list", registry, repository);
+    let mut token = None;
+    let mut tags = Vec::new();
+    loop {
+        let mut response = fetch(&url, token.as_deref())?;
+        if response.status == 401 && token.is_none() {
+            if let Some(challenge) = response.header("www-authenticate") {
+                token = Some(fetch_token(challenge, registry)?);
+                response = fetch(&url, token.as_deref())?;
+            }
+        }
+        if !(200..300).contains(&response.status) {
+            return Err(NanoServiceError::new(
+                format!("Failed to list the image tags from {}: HTTP status {}", url, response.status),
+                NanoServiceErrorStatus::Unknown
+            ))
+        }
+        let body: Value = safe_eject!(
+            serde_json::from_str(&response.body),
+            NanoServiceErrorStatus::Unknown,
+            format!("Failed to parse the image tags from {}", url)
+        )?;
+        tags.extend(string_array(&body["tags"]));
+        match response.header("link").and_then(next_link) {
+            Some(next) if next.starts_with('/') => url = format!("https://{}{}", registry, next),
+            Some(next) => url = next,
+            None => break
+        }
+    }
+    Ok(tags)
+}
+
+
+/// A response of the registry API.
+///
+/// # Fields
+/// * `status` - The HTTP status code.
+/// * `headers` - The headers keyed by their lowercase name.
+/// * `body` - The body of the response.
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+
+impl Response {
+
+    /// Parses the output of `curl --include`.
+    ///
+    /// # Arguments
+    /// * `output` - The status line and the headers followed by an empty line and the body.
+    ///
+    /// # Returns
+    /// The parsed response, or `None` if the output has no status line.
+    fn parse(output: &str) -> Option<Response> {
+        let (head, body) = output.split_once("\r\n\r\n").or_else(|| output.split_once("\n\n"))?;
+        let mut lines = head.lines();
+        let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        Some(Response { status, headers, body: body.to_string() })
+    }
+
+    /// Gets a header of the response.
+    ///
+    /// # Arguments
+    /// * `name` - The lowercase name of the header.
+    ///
+    /// # Returns
+    /// The value of the header if the response has it.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|value| value.as_str())
+    }
+
+}
+
+
+/// Fetches a page of the registry API with `curl`.
+///
+/// # Arguments
+/// * `url` - The URL to fetch.
+/// * `token` - The bearer token to authenticate with.
+///
+/// # Returns
+/// The response of the registry, whatever its status.
+fn fetch(url: &str, token: Option<&str>) -> Result<Response, NanoServiceError> {
+    let mut command = Command::new("curl");
+    command.args(["-sS", "--include", url]);
+    if let Some(token) = token {
+        command.args(["-H", &format!("Authorization: Bearer {}", token)]);
+    }
+    let output = safe_eject!(
+        command.output(),
+        NanoServiceErrorStatus::Unknown,
+        "Failed to run curl to list the image tags in NanoForge"
+    )?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match output.status.success().then(|| Response::parse(&stdout)).flatten() {
+        Some(response) => Ok(response),
+        None => Err(NanoServiceError::new(
+            format!(
+                "Failed to list the image tags from {}: {}",
+                url, String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            NanoServiceErrorStatus::Unknown
+        ))
+    }
+}
+
+
+/// Fetches a bearer token for the registry API.
+///
+/// # Arguments
+/// * `challenge` - The `WWW-Authenticate` header of the `401` response.
+/// * `registry` - The registry to look up the stored credentials of.
+///
+/// # Returns
+/// The token issued by the realm of the challenge.
+fn fetch_token(challenge: &str, registry: &str) -> Result<String, NanoServiceError> {
+    let parameters = parse_challenge(challenge).ok_or_else(|| NanoServiceError::new(
+        format!("Unsupported authentication challenge from {}: {}", registry, challenge),
+        NanoServiceErrorStatus::Unauthorized
+    ))?;
+    let mut command = Command::new("curl");
+    command.args(["-sSfL", "-G", &parameters["realm"]]);
+    for key in ["service", "scope"] {
+        if let Some(value) = parameters.get(key) {
+            command.args(["--data-urlencode", &format!("{}={}", key, value)]);
+        }
+    }
+    if let Some(auth) = stored_auth(registry) {
+        command.args(["-H", &format!("Authorization: Basic {}", auth)]);
+    }
+    let output = safe_eject!(
+        command.output(),
+        NanoServiceErrorStatus::Unknown,
+        "Failed to run curl to fetch a registry token in NanoForge"
+    )?;
+    if !output.status.success() {
+        return Err(NanoServiceError::new(
+            format!(
+                "Failed to fetch a token for {} from {}: {}",
+                registry, parameters["realm"], String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            NanoServiceErrorStatus::Unauthorized
+        ))
+    }
+    let response: Value = safe_eject!(
+        serde_json::from_slice(&output.stdout),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to parse the token from {}", parameters["realm"])
+    )?;
+    match response["token"].as_str().or(response["access_token"].as_str()) {
+        Some(token) => Ok(token.to_string()),
+        None => Err(NanoServiceError::new(
+            format!("No token was issued for {} by {}", registry, parameters["realm"]),
+            NanoServiceErrorStatus::Unauthorized
+        ))
+    }
+}
+
+
+/// Parses a `Bearer` challenge such as `Bearer realm="https://auth.example.com/token",service="registry"`.
+///
+/// # Arguments
+/// * `challenge` - The value of the `WWW-Authenticate` header.
+///
+/// # Returns
+/// The parameters of the challenge, or `None` if it is not a `Bearer` challenge with a realm.
+fn parse_challenge(challenge: &str) -> Option<HashMap<String, String>> {
+    let (scheme, mut rest) = challenge.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None
+    }
+    let mut parameters = HashMap::new();
+    while let Some((key, after)) = rest.split_once('=') {
+        // quoted values can contain commas (`scope="repository:team/nan-one:pull,push"`)
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after.split_once(',').unwrap_or((after, ""))
+        };
+        parameters.insert(key.trim().to_lowercase(), value.to_string());
+        rest = after.trim_start_matches([',', ' ']);
+    }
+    parameters.contains_key("realm").then_some(parameters)
+}
+
+
+/// Gets the URL of the next page from a `Link` header such as `</v2/nan-one/tags/list?last=1.0.0>; rel="next"`.
+///
+/// # Arguments
+/// * `link` - The value of the `Link` header.
+///
+/// # Returns
+/// The URL of the next page if the header links to one.
+fn next_link(link: &str) -> Option<String> {
+    link.split(',')
+        .find(|part| part.split(';').skip(1).any(|parameter| parameter.replace(['"', ' '], "") == "rel=next"))
+        .and_then(|part| {
+            let part = part.trim();
+            Some(part[part.find('<')? + 1..part.find('>')?].to_string())
+        })
+}
+
+
+/// Gets the credentials that `docker login` stored for a registry in the Docker config file.
+///
+/// # Arguments
+/// * `registry` - The registry to get the credentials of.
+///
+/// # Returns
+/// The base64 encoded `user:password` of the registry, or `None` if none are stored in the file.
+fn stored_auth(registry: &str) -> Option<String> {
+    let directory = match std::env::var("DOCKER_CONFIG") {
+        Ok(directory) => PathBuf::from(directory),
+        Err(_) => PathBuf::from(std::env::var("HOME").ok()?).join(".docker")
+    };
+    let config: Value = serde_json::from_str(&std::fs::read_to_string(directory.join("config.json")).ok()?).ok()?;
+    config["auths"][registry]["auth"].as_str().map(|auth| auth.to_string())
+}
+
+
+/// Fetches a JSON document with `curl`.
+///
+/// # Arguments
+/// * `url` - The URL to fetch.
+///
+/// # Returns
+/// The parsed JSON document.
+fn fetch_json(url: &str) -> Result<Value, NanoServiceError> {
+    let output = safe_eject!(
+        Command::new("curl").args(["-sSfL", url]).output(),
+        NanoServiceErrorStatus::Unknown,
+        "Failed to run curl to list the image tags in NanoForge"
+    )?;
+    if !output.status.success() {
+        return Err(NanoServiceError::new(
+            format!(
+                "Failed to list the image tags from {}: {}",
+                url, String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            NanoServiceErrorStatus::Unknown
+        ))
+    }
+    let response = safe_eject!(
+        serde_json::from_slice(&output.stdout),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to parse the image tags from {}", url)
+    )?;
+    Ok(response)
+}
+
+
+/// Collects the strings in a JSON array (anything else is ignored).
+fn string_array(value: &Value) -> Vec<String> {
+    value.as_array().into_iter().flatten()
+        .filter_map(|value| value.as_str().map(|value| value.to_string()))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_image_reference() {
+        assert_eq!(
+            ImageReference {
+                registry: None,
+                repository: "maxwellflitton/nan-one".to_string(),
+                tag: Some("1.0.0".to_string())
+            },
+            ImageReference::parse("maxwellflitton/nan-one:1.0.0")
+        );
+        assert_eq!(
+            ImageReference {
+                registry: Some("localhost:5000".to_string()),
+                repository: "team/nan-one".to_string(),
+                tag: None
+            },
+            ImageReference::parse("localhost:5000/team/nan-one")
+        );
+        assert_eq!(
+            "registry.example.com/nan-one",
+            ImageReference::parse("registry.example.com/nan-one:latest").name()
+        );
+    }
+
+    #[test]
+    fn test_mirror_image() {
+        let mirrors = HashMap::from([
+            ("docker.io".to_string(), "mirror.example.com/hub".to_string()),
+        ]);
+
+        assert_eq!("mirror.example.com/hub/maxwellflitton/nan-one:1.0.0", mirror_image("maxwellflitton/nan-one:1.0.0", &mirrors));
+        assert_eq!("mirror.example.com/hub/library/nginx", mirror_image("nginx", &mirrors));
+        assert_eq!("localhost:5000/nan-one:1.0.0", mirror_image("localhost:5000/nan-one:1.0.0", &mirrors));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = Response::parse(
+            "HTTP/2 401\r\nWWW-Authenticate: Bearer realm=\"https://auth.example.com/token\"\r\n\r\n{\"errors\":[]}"
+        ).unwrap();
+
+        assert_eq!(401, response.status);
+        assert_eq!(Some("Bearer realm=\"https://auth.example.com/token\""), response.header("www-authenticate"));
+        assert_eq!("{\"errors\":[]}", response.body);
+        assert_eq!(None, Response::parse("not a response"));
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let parameters = parse_challenge(
+            "Bearer realm=\"https://auth.example.com/token\",service=\"registry.example.com\",scope=\"repository:team/nan-one:pull,push\""
+        ).unwrap();
+
+        assert_eq!("https://auth.example.com/token", parameters["realm"]);
+        assert_eq!("registry.example.com", parameters["service"]);
+        assert_eq!("repository:team/nan-one:pull,push", parameters["scope"]);
+        assert_eq!(None, parse_challenge("Basic realm=\"registry\""));
+    }
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(
+            Some("/v2/team/nan-one/tags/list?n=100&last=1.0.0".to_string()),
+            next_link("</v2/team/nan-one/tags/list?n=100&last=1.0.0>; rel=\"next\"")
+        );
+        assert_eq!(None, next_link("</v2/team/nan-one/tags/list?n=100&last=1.0.0>; rel=\"prev\""));
+    }
+
+}
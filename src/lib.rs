@@ -9,3 +9,7 @@ pub mod docker_files;
 pub mod toml_operations;
 #[allow(dead_code)]
 pub mod builds;
+#[allow(dead_code)]
+pub mod project_config;
+#[allow(dead_code)]
+pub mod lockfile;
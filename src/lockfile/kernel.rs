@@ -0,0 +1,63 @@
+//! The data structs for the lockfile.
+use serde::{Deserialize, Serialize};
+
+
+/// Represents the structure of the `nanoforge.lock` file in the root of the project.
+///
+/// # Fields
+/// * `image` - The resolved images, one entry per image and version requirement.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub image: Vec<LockedImage>,
+}
+
+
+/// Represents an image whose version requirement has been resolved to a tag.
+///
+/// # Fields
+/// * `name` - The image without a tag.
+/// * `version` - The version requirement declared for the image.
+/// * `tag` - The tag the version requirement was resolved to.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct LockedImage {
+    pub name: String,
+    pub version: String,
+    pub tag: String,
+}
+
+
+impl Lockfile {
+
+    /// Gets the locked tag of an image for a version requirement.
+    ///
+    /// # Arguments
+    /// * `name` - The image without a tag.
+    /// * `version` - The version requirement declared for the image.
+    ///
+    /// # Returns
+    /// The locked tag if the image has been resolved for the version requirement.
+    pub fn get(&self, name: &str, version: &str) -> Option<&String> {
+        self.image.iter()
+            .find(|locked| locked.name == name && locked.version == version)
+            .map(|locked| &locked.tag)
+    }
+
+    /// Records the tag that an image was resolved to for a version requirement, replacing the previous tag.
+    ///
+    /// # Arguments
+    /// * `name` - The image without a tag.
+    /// * `version` - The version requirement declared for the image.
+    /// * `tag` - The tag the version requirement was resolved to.
+    pub fn insert(&mut self, name: &str, version: &str, tag: &str) {
+        self.image.retain(|locked| !(locked.name == name && locked.version == version));
+        self.image.push(LockedImage {
+            name: name.to_string(),
+            version: version.to_string(),
+            tag: tag.to_string(),
+        });
+        // keep the file stable between runs so it diffs cleanly in version control
+        self.image.sort_by(|one, two| (&one.name, &one.version).cmp(&(&two.name, &two.version)));
+    }
+
+}
@@ -0,0 +1,4 @@
+//! Defines the `nanoforge.lock` file that records the image tags that version requirements were resolved to.
+pub mod kernel;
+pub mod read;
+pub mod write;
@@ -0,0 +1,72 @@
+//! Defines the reading of the `nanoforge.lock` file.
+use std::fs;
+use std::path::Path;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+use crate::docker_files::cache::CURRENT_WORKING_DIR;
+use crate::lockfile::kernel::Lockfile;
+
+
+/// The name of the lockfile.
+pub const LOCKFILE: &str = "nanoforge.lock";
+
+
+/// Reads the `nanoforge.lock` file in the current working directory.
+///
+/// # Returns
+/// The lockfile, or an empty lockfile if there is no `nanoforge.lock` file.
+pub fn read_lockfile() -> Result<Lockfile, NanoServiceError> {
+    read_lockfile_from(&CURRENT_WORKING_DIR.join(LOCKFILE))
+}
+
+
+/// Reads a lockfile.
+///
+/// # Arguments
+/// * `path` - The path to the lockfile.
+///
+/// # Returns
+/// The lockfile, or an empty lockfile if the file does not exist.
+pub fn read_lockfile_from(path: &Path) -> Result<Lockfile, NanoServiceError> {
+    if !path.exists() {
+        return Ok(Lockfile::default())
+    }
+    let contents = safe_eject!(
+        fs::read_to_string(path),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to read lockfile: {}", path.display())
+    )?;
+    let lockfile: Lockfile = safe_eject!(
+        toml::from_str(&contents),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to parse lockfile: {}", path.display())
+    )?;
+    Ok(lockfile)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_read_lockfile() {
+        let lockfile = read_lockfile_from(Path::new("./tests/assets/nanoforge.lock")).unwrap();
+
+        assert_eq!(Some(&"1.4.3".to_string()), lockfile.get("maxwellflitton/nan-one", "^1.4"));
+        assert_eq!(None, lockfile.get("maxwellflitton/nan-one", "^2"));
+    }
+
+    #[test]
+    fn test_read_lockfile_missing() {
+        let lockfile = read_lockfile_from(Path::new("./tests/assets/not_here.lock")).unwrap();
+        assert_eq!(Lockfile::default(), lockfile);
+    }
+
+}
@@ -0,0 +1,44 @@
+//! Defines the writing of the `nanoforge.lock` file.
+use std::fs;
+use std::path::Path;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+use crate::docker_files::cache::CURRENT_WORKING_DIR;
+use crate::lockfile::{
+    kernel::Lockfile,
+    read::LOCKFILE
+};
+
+
+/// Writes the `nanoforge.lock` file in the current working directory.
+///
+/// # Arguments
+/// * `lockfile` - The lockfile to write.
+pub fn write_lockfile(lockfile: &Lockfile) -> Result<(), NanoServiceError> {
+    write_lockfile_to(&CURRENT_WORKING_DIR.join(LOCKFILE), lockfile)
+}
+
+
+/// Writes a lockfile.
+///
+/// # Arguments
+/// * `path` - The path to write the lockfile to.
+/// * `lockfile` - The lockfile to write.
+pub fn write_lockfile_to(path: &Path, lockfile: &Lockfile) -> Result<(), NanoServiceError> {
+    let contents = safe_eject!(
+        toml::to_string(lockfile),
+        NanoServiceErrorStatus::Unknown,
+        "Failed to serialize lockfile"
+    )?;
+    safe_eject!(
+        fs::write(path, format!("# This file is generated by NanoForge, do not edit.\n{}", contents)),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to write lockfile: {}", path.display())
+    )?;
+    Ok(())
+}
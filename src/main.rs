@@ -1,21 +1,34 @@
 mod toml_operations;
 mod docker_files;
 mod builds;
+mod project_config;
+mod lockfile;
 
+use std::path::Path;
 use nanoservices_utils::errors::{
     NanoServiceError,
     NanoServiceErrorStatus
 };
+use project_config::config::load_config;
+use project_config::kernel::ProjectConfig;
 use toml_operations::nanoservices::processes::{
     prep::recursive_prep_nanoservices,
-    graph::graph_nanos,
+    graph::{graph_nanos, GraphFormat},
     config::recursive_config_nanoservices,
-    install::recurrsive_install_nanoservices
+    install::recurrsive_install_nanoservices,
+    unprep::unprep_nanoservices,
+    dry_run::dry_run_nanoservices,
+    check::check_nanoservices,
+    update::update_nanoservices,
+    status::status_nanoservices,
+    why::why_nanoservice,
+    tree::{tree_nanos, TreeOptions}
 };
 
 
 fn main() -> Result<(), NanoServiceError> {
-    println!("Hello, nanoservices!! .... That's so dingo!");
+    // the greeting goes to stderr so the diff of a dry run can be piped
+    eprintln!("Hello, nanoservices!! .... That's so dingo!");
     // get the first argument passed to the program
     let args: Vec<String> = std::env::args().collect();
     let command = match args.get(1) {
@@ -27,22 +40,106 @@ fn main() -> Result<(), NanoServiceError> {
             ))
         }
     };
+    // writes shim crates into the cache instead of writing to the tracked Cargo.toml files
+    let non_invasive = args.iter().any(|arg| arg == "--non-invasive");
+    // prints what prep or config would change without writing or pulling anything
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    // selects the image of every nanoservice, falls back to NANOFORGE_PROFILE and the nanoforge.toml file
+    let cli_profile = flag_value(&args, "--profile")?;
+    // never pulls images or contacts registries, falls back to NANOFORGE_OFFLINE and the nanoforge.toml file
+    let offline = args.iter().any(|arg| arg == "--offline");
+    // scaffolding a new nanoservice does not read the config so a broken nanoforge.toml file cannot stop it
+    if command == "new" {
+        let name = match args.get(2) {
+            Some(v) => v,
+            _ => {
+                return Err(NanoServiceError::new(
+                    "No name specified".to_string(),
+                    NanoServiceErrorStatus::Unknown
+                ))
+            }
+        };
+        builds::nanoservice::create_new_nanoservice(name.to_string())?;
+        return Ok(())
+    }
+    // the config is loaded once from the user config, the nanoforge.toml file, the environment, and the flags
+    let config = load_config(ProjectConfig {
+        profile: cli_profile.cloned(),
+        offline: offline.then_some(true),
+        ..ProjectConfig::default()
+    })?;
     // dependency management
-    if command == "prep" {
+    if command == "prep" && dry_run {
+        eprintln!("prepping nanos (dry run)");
+        dry_run_nanoservices(true, non_invasive, &config)?;
+    }
+    else if command == "prep" {
         println!("prepping nanos");
-        recursive_prep_nanoservices()?;
+        recursive_prep_nanoservices(non_invasive, &config)?;
     }
     else if command == "graph" {
         println!("graphing nanos");
-        graph_nanos()?;
+        // the format defaults to a png image, the dot, json, mermaid, and text formats do not need graphviz
+        let format = match flag_value(&args, "--format")? {
+            Some(format) => format.parse()?,
+            None => GraphFormat::default()
+        };
+        let output = flag_value(&args, "--output")?;
+        graph_nanos(format, output.map(Path::new), &config)?;
+    }
+    else if command == "tree" {
+        let depth = match flag_value(&args, "--depth")? {
+            Some(depth) => Some(depth.parse::<usize>().map_err(|_| NanoServiceError::new(
+                format!("Invalid depth `{}`, expected a number", depth),
+                NanoServiceErrorStatus::BadRequest
+            ))?),
+            None => None
+        };
+        let options = TreeOptions {
+            invert: flag_value(&args, "--invert")?.cloned(),
+            depth,
+            duplicates: args.iter().any(|arg| arg == "--duplicates"),
+        };
+        tree_nanos(&options, &config)?;
+    }
+    else if command == "status" {
+        status_nanoservices(non_invasive, &config)?;
+    }
+    else if command == "why" {
+        let target = match args.get(2) {
+            Some(v) => v,
+            _ => {
+                return Err(NanoServiceError::new(
+                    "No nanoservice or image specified".to_string(),
+                    NanoServiceErrorStatus::Unknown
+                ))
+            }
+        };
+        why_nanoservice(target, &config)?;
+    }
+    else if command == "config" && args.iter().any(|arg| arg == "--check") {
+        println!("checking nanos");
+        check_nanoservices(non_invasive, &config)?;
+    }
+    else if command == "config" && dry_run {
+        eprintln!("configuring nanos (dry run)");
+        dry_run_nanoservices(false, non_invasive, &config)?;
     }
     else if command == "config" {
         println!("configuring nanos");
-        recursive_config_nanoservices()?;
+        recursive_config_nanoservices(non_invasive, &config)?;
+    }
+    else if command == "unprep" {
+        println!("unprepping nanos");
+        unprep_nanoservices(args.iter().any(|arg| arg == "--wipe-cache"), &config)?;
+    }
+    else if command == "update" {
+        println!("updating nanos");
+        update_nanoservices(&config)?;
     }
     else if command == "install" {
         println!("installing nanos");
-        recurrsive_install_nanoservices()?;
+        recurrsive_install_nanoservices(&config)?;
     }
     else if command == "pull" {
         let image = match args.get(2) {
@@ -56,22 +153,62 @@ fn main() -> Result<(), NanoServiceError> {
             },
         };
 
-        let _path = docker_files::download_nanoservice(image)?;
-    }
-    else if command == "new" {
-        let name = match args.get(2) {
-            Some(v) => v,
-            _ => {
-                return Err(NanoServiceError::new(
-                    "No name specified".to_string(),
-                    NanoServiceErrorStatus::Unknown
-                ))
-            }
-        };
-        builds::nanoservice::create_new_nanoservice(name.to_string())?;
+        let _path = docker_files::download_nanoservice(image, &config)?;
     }
     else {
         println!("Command not found");
     }
     Ok(())
 }
+
+
+/// Gets the value that follows a flag on the command line.
+///
+/// # Arguments
+/// * `args` - The command line arguments.
+/// * `flag` - The flag to get the value of.
+///
+/// # Returns
+/// The value of the flag, or `None` if the flag is not passed. An error if the flag is passed without a value or
+/// is followed by another flag.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a String>, NanoServiceError> {
+    let index = match args.iter().position(|arg| arg == flag) {
+        Some(index) => index,
+        None => return Ok(None)
+    };
+    match args.get(index + 1) {
+        Some(value) if !value.starts_with("--") => Ok(Some(value)),
+        _ => Err(NanoServiceError::new(
+            format!("No value specified for {}", flag),
+            NanoServiceErrorStatus::BadRequest
+        ))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_flag_value() {
+        let prep = args(&["nanoforge", "prep", "--profile", "staging", "--dry-run"]);
+        assert_eq!(Some(&"staging".to_string()), flag_value(&prep, "--profile").unwrap());
+        assert_eq!(None, flag_value(&prep, "--format").unwrap());
+
+        assert_eq!(
+            "No value specified for --profile",
+            flag_value(&args(&["nanoforge", "prep", "--profile", "--dry-run"]), "--profile").unwrap_err().message
+        );
+        assert_eq!(
+            "No value specified for --depth",
+            flag_value(&args(&["nanoforge", "tree", "--depth"]), "--depth").unwrap_err().message
+        );
+    }
+
+}
@@ -0,0 +1,286 @@
+//! Defines the loading of the configuration that is passed to every process.
+//!
+//! # Notes
+//! The configuration is layered with the later layers taking precedence:
+//! 1. the user-level config file (`$XDG_CONFIG_HOME/nanoforge/config.toml` or `~/.config/nanoforge/config.toml`)
+//! 2. the `nanoforge.toml` file in the root of the project
+//! 3. the `NANOFORGE_*` environment variables
+//! 4. the command line flags
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+use crate::docker_files::cache::{CACHE_DIR, CURRENT_WORKING_DIR};
+use crate::project_config::kernel::{
+    ConflictPolicy,
+    DiscoveryConfig,
+    ImageBackend,
+    ProjectConfig,
+    PullPolicy,
+    UnifyPolicy
+};
+use crate::project_config::profile::Profile;
+use crate::project_config::read::{read_project_config, read_project_config_from};
+
+
+/// The environment variable that selects the profile.
+pub const PROFILE_ENV_VAR: &str = "NANOFORGE_PROFILE";
+
+/// The environment variable that sets the cache directory.
+pub const CACHE_DIR_ENV_VAR: &str = "NANOFORGE_CACHE_DIR";
+
+/// The environment variable that selects the image backend.
+pub const BACKEND_ENV_VAR: &str = "NANOFORGE_BACKEND";
+
+/// The environment variable that sets the pull policy.
+pub const PULL_ENV_VAR: &str = "NANOFORGE_PULL";
+
+/// The environment variable that turns on the offline mode.
+pub const OFFLINE_ENV_VAR: &str = "NANOFORGE_OFFLINE";
+
+/// The environment variable that sets how inconsistent nanoservice declarations are reported.
+pub const CONFLICTS_ENV_VAR: &str = "NANOFORGE_CONFLICTS";
+
+/// The environment variable that sets which tag is chosen when an image is declared with different tags.
+pub const UNIFY_ENV_VAR: &str = "NANOFORGE_UNIFY";
+
+
+/// The configuration of NanoForge after all the layers have been merged.
+///
+/// # Fields
+/// * `profile` - The active profile that selects the images of the nanoservices.
+/// * `cache_dir` - The absolute path to the directory that the images are unpacked into.
+/// * `backend` - The container engine that pulls and saves the images.
+/// * `mirrors` - Mirrors to pull the images of a registry from, keyed by the registry.
+/// * `discovery` - How the `Cargo.toml` files are found and the globs that select them.
+/// * `pull` - When the images are pulled (always `Never` in the offline mode).
+/// * `offline` - If `true` no images are pulled and no registries are contacted.
+/// * `conflicts` - If inconsistent nanoservice declarations are reported as warnings or errors.
+/// * `unify` - Which tag is chosen when an image is declared with different tags.
+/// * `vars` - The variables that can be referenced in the image references of nanoservices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NanoforgeConfig {
+    pub profile: Profile,
+    pub cache_dir: PathBuf,
+    pub backend: ImageBackend,
+    pub mirrors: HashMap<String, String>,
+    pub discovery: DiscoveryConfig,
+    pub pull: PullPolicy,
+    pub offline: bool,
+    pub conflicts: ConflictPolicy,
+    pub unify: UnifyPolicy,
+    pub vars: HashMap<String, String>,
+}
+
+
+impl Default for NanoforgeConfig {
+    fn default() -> Self {
+        NanoforgeConfig {
+            profile: Profile::default(),
+            cache_dir: CACHE_DIR.clone(),
+            backend: ImageBackend::default(),
+            mirrors: HashMap::new(),
+            discovery: DiscoveryConfig::default(),
+            pull: PullPolicy::default(),
+            offline: false,
+            conflicts: ConflictPolicy::default(),
+            unify: UnifyPolicy::default(),
+            vars: HashMap::new(),
+        }
+    }
+}
+
+
+impl NanoforgeConfig {
+
+    /// Resolves the merged layers into the configuration.
+    ///
+    /// # Arguments
+    /// * `config` - The merged layers.
+    ///
+    /// # Returns
+    /// The configuration with the defaults filled in.
+    pub fn from_layers(config: ProjectConfig) -> Result<NanoforgeConfig, NanoServiceError> {
+        let profile = match config.profile {
+            Some(profile) => profile.parse()?,
+            None => Profile::default()
+        };
+        let offline = config.offline.unwrap_or(false);
+        Ok(NanoforgeConfig {
+            profile,
+            // an absolute cache directory replaces the current working directory in the join
+            cache_dir: match config.cache_dir {
+                Some(cache_dir) => CURRENT_WORKING_DIR.join(cache_dir),
+                None => CACHE_DIR.clone()
+            },
+            backend: config.backend.unwrap_or_default(),
+            mirrors: config.mirrors.unwrap_or_default(),
+            discovery: config.discovery.unwrap_or_default(),
+            pull: match offline {
+                true => PullPolicy::Never,
+                false => config.pull.unwrap_or_default()
+            },
+            offline,
+            conflicts: config.conflicts.unwrap_or_default(),
+            unify: config.unify.unwrap_or_default(),
+            vars: config.vars.unwrap_or_default(),
+        })
+    }
+
+}
+
+
+/// Gets the path to the user-level config file.
+///
+/// # Arguments
+/// * `lookup` - Looks up an environment variable.
+///
+/// # Returns
+/// The path to the user-level config file, or `None` if neither `XDG_CONFIG_HOME` nor `HOME` are set.
+pub fn user_config_path(lookup: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    match lookup("XDG_CONFIG_HOME") {
+        Some(config_home) => Some(PathBuf::from(config_home)),
+        None => lookup("HOME").map(|home| PathBuf::from(home).join(".config"))
+    }.map(|config_dir| config_dir.join("nanoforge").join("config.toml"))
+}
+
+
+/// Parses the value of an environment variable with the same names that are used in the config files.
+///
+/// # Arguments
+/// * `key` - The name of the environment variable (used in the error).
+/// * `value` - The value of the environment variable.
+///
+/// # Returns
+/// The parsed value.
+fn parse_env_var<T: DeserializeOwned>(key: &str, value: &str) -> Result<T, NanoServiceError> {
+    T::deserialize(value.into_deserializer()).map_err(|error: serde::de::value::Error| {
+        NanoServiceError::new(
+            format!("Invalid value `{}` for {}: {}", value, key, error),
+            NanoServiceErrorStatus::BadRequest
+        )
+    })
+}
+
+
+/// Reads the configuration layer of the `NANOFORGE_*` environment variables.
+///
+/// # Arguments
+/// * `lookup` - Looks up an environment variable.
+///
+/// # Returns
+/// The configuration layer with the settings that have an environment variable set.
+pub fn env_config(lookup: impl Fn(&str) -> Option<String>) -> Result<ProjectConfig, NanoServiceError> {
+    let offline = match lookup(OFFLINE_ENV_VAR) {
+        Some(value) => match value.as_str() {
+            "1" | "true" => Some(true),
+            "0" | "false" => Some(false),
+            _ => {
+                return Err(NanoServiceError::new(
+                    format!("Invalid value `{}` for {}: expected `true` or `false`", value, OFFLINE_ENV_VAR),
+                    NanoServiceErrorStatus::BadRequest
+                ))
+            }
+        },
+        None => None
+    };
+    Ok(ProjectConfig {
+        profile: lookup(PROFILE_ENV_VAR),
+        cache_dir: lookup(CACHE_DIR_ENV_VAR),
+        backend: lookup(BACKEND_ENV_VAR).map(|value| parse_env_var(BACKEND_ENV_VAR, &value)).transpose()?,
+        pull: lookup(PULL_ENV_VAR).map(|value| parse_env_var(PULL_ENV_VAR, &value)).transpose()?,
+        offline,
+        conflicts: lookup(CONFLICTS_ENV_VAR).map(|value| parse_env_var(CONFLICTS_ENV_VAR, &value)).transpose()?,
+        unify: lookup(UNIFY_ENV_VAR).map(|value| parse_env_var(UNIFY_ENV_VAR, &value)).transpose()?,
+        ..ProjectConfig::default()
+    })
+}
+
+
+/// Loads the configuration from all the layers.
+///
+/// # Arguments
+/// * `cli` - The configuration layer of the command line flags.
+///
+/// # Returns
+/// The configuration that is passed to every process.
+pub fn load_config(cli: ProjectConfig) -> Result<NanoforgeConfig, NanoServiceError> {
+    let lookup = |key: &str| std::env::var(key).ok();
+    let user = match user_config_path(lookup) {
+        Some(path) => read_project_config_from(&path)?,
+        None => ProjectConfig::default()
+    };
+    NanoforgeConfig::from_layers(
+        user.merge(read_project_config()?).merge(env_config(lookup)?).merge(cli)
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::path::Path;
+
+    fn lookup(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |key| vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string())
+    }
+
+    #[test]
+    fn test_layers_precedence() {
+        let user = ProjectConfig {
+            profile: Some("staging".to_string()),
+            backend: Some(ImageBackend::Podman),
+            vars: Some(HashMap::from([("NANO_TAG".to_string(), "1.0.0".to_string())])),
+            ..ProjectConfig::default()
+        };
+        let project = read_project_config_from(Path::new("./tests/assets/nanoforge.toml")).unwrap();
+        let env = env_config(lookup(&[(PULL_ENV_VAR, "missing"), (CACHE_DIR_ENV_VAR, "/tmp/nanoforge")])).unwrap();
+        let cli = ProjectConfig {
+            profile: Some("canary".to_string()),
+            ..ProjectConfig::default()
+        };
+
+        let config = NanoforgeConfig::from_layers(user.merge(project).merge(env).merge(cli)).unwrap();
+
+        assert_eq!("canary", config.profile.name());
+        assert_eq!(ImageBackend::Podman, config.backend);
+        assert_eq!(PullPolicy::Missing, config.pull);
+        assert_eq!(PathBuf::from("/tmp/nanoforge"), config.cache_dir);
+        assert_eq!("1.4.2", config.vars.get("NANO_TAG").unwrap());
+        assert_eq!("registry.example.com", config.vars.get("NANO_REGISTRY").unwrap());
+    }
+
+    #[test]
+    fn test_offline_never_pulls() {
+        let env = env_config(lookup(&[(OFFLINE_ENV_VAR, "1"), (PULL_ENV_VAR, "always")])).unwrap();
+        let config = NanoforgeConfig::from_layers(env).unwrap();
+
+        assert_eq!(true, config.offline);
+        assert_eq!(PullPolicy::Never, config.pull);
+    }
+
+    #[test]
+    fn test_invalid_env_var() {
+        assert_eq!(
+            "Invalid value `docker-desktop` for NANOFORGE_BACKEND: unknown variant `docker-desktop`, expected `docker` or `podman`",
+            env_config(lookup(&[(BACKEND_ENV_VAR, "docker-desktop")])).unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn test_user_config_path() {
+        assert_eq!(
+            Some(PathBuf::from("/home/dev/.config/nanoforge/config.toml")),
+            user_config_path(lookup(&[("HOME", "/home/dev")]))
+        );
+        assert_eq!(
+            Some(PathBuf::from("/etc/xdg/nanoforge/config.toml")),
+            user_config_path(lookup(&[("HOME", "/home/dev"), ("XDG_CONFIG_HOME", "/etc/xdg")]))
+        );
+    }
+
+}
@@ -0,0 +1,189 @@
+//! The data structs for the project configuration.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+
+/// Represents the structure of the `nanoforge.toml` file in the root of the project.
+///
+/// # Fields
+/// * `vars` - Variables that can be referenced in the image references of nanoservices with `${NAME}`.
+/// * `profile` - The default profile when neither `--profile` nor `NANOFORGE_PROFILE` are set.
+/// * `discovery` - The globs that select the `Cargo.toml` files that are searched for nanoservices.
+/// * `cache_dir` - The directory that the images are unpacked into, relative to the project root if not absolute.
+/// * `backend` - The container engine that pulls and saves the images.
+/// * `mirrors` - Mirrors to pull the images of a registry from, keyed by the registry (`docker.io` for Docker Hub).
+/// * `pull` - When the images are pulled.
+/// * `offline` - If `true` no images are pulled and no registries are contacted.
+/// * `conflicts` - If inconsistent nanoservice declarations are reported as warnings or errors.
+/// * `unify` - Which tag is chosen when an image is declared with different tags.
+///
+/// # Notes
+/// The same structure is used for the user-level config file and the `NANOFORGE_*` environment variables so the
+/// layers can be merged with `ProjectConfig::merge`. Unknown keys are rejected so a misspelled setting is not
+/// silently ignored.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub vars: Option<HashMap<String, String>>,
+    pub profile: Option<String>,
+    pub discovery: Option<DiscoveryConfig>,
+    pub cache_dir: Option<String>,
+    pub backend: Option<ImageBackend>,
+    pub mirrors: Option<HashMap<String, String>>,
+    pub pull: Option<PullPolicy>,
+    pub offline: Option<bool>,
+    pub conflicts: Option<ConflictPolicy>,
+    pub unify: Option<UnifyPolicy>,
+}
+
+
+impl ProjectConfig {
+
+    /// Layers another configuration on top of this one.
+    ///
+    /// # Notes
+    /// The settings of the other configuration take precedence, the `vars` and `mirrors` tables are merged key by key.
+    ///
+    /// # Arguments
+    /// * `other` - The configuration with the higher precedence.
+    ///
+    /// # Returns
+    /// The merged configuration.
+    pub fn merge(self, other: ProjectConfig) -> ProjectConfig {
+        ProjectConfig {
+            vars: merge_tables(self.vars, other.vars),
+            profile: other.profile.or(self.profile),
+            discovery: match (self.discovery, other.discovery) {
+                (Some(base), Some(other)) => Some(DiscoveryConfig {
+                    include: other.include.or(base.include),
+                    exclude: other.exclude.or(base.exclude),
+                    mode: other.mode.or(base.mode),
+                }),
+                (base, other) => other.or(base)
+            },
+            cache_dir: other.cache_dir.or(self.cache_dir),
+            backend: other.backend.or(self.backend),
+            mirrors: merge_tables(self.mirrors, other.mirrors),
+            pull: other.pull.or(self.pull),
+            offline: other.offline.or(self.offline),
+            conflicts: other.conflicts.or(self.conflicts),
+            unify: other.unify.or(self.unify),
+        }
+    }
+
+}
+
+
+/// Merges two tables with the entries of the other table taking precedence.
+fn merge_tables(
+        base: Option<HashMap<String, String>>,
+        other: Option<HashMap<String, String>>
+    ) -> Option<HashMap<String, String>> {
+    match (base, other) {
+        (Some(mut base), Some(other)) => {
+            base.extend(other);
+            Some(base)
+        },
+        (base, other) => other.or(base)
+    }
+}
+
+
+/// Represents the `[discovery]` table of the `nanoforge.toml` file.
+///
+/// # Fields
+/// * `include` - If set, only the `Cargo.toml` files matching one of these globs are searched.
+/// * `exclude` - The `Cargo.toml` files matching one of these globs are not searched.
+/// * `mode` - How the `Cargo.toml` files are found, walking the directory tree if not set.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub mode: Option<DiscoveryMode>,
+}
+
+
+/// How the `Cargo.toml` files of the project are found.
+///
+/// # Variants
+/// * `Walk` - Every `Cargo.toml` in the directory tree is found.
+/// * `Workspace` - Only the root `Cargo.toml` and the members of its `[workspace]` are found.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscoveryMode {
+    #[default]
+    Walk,
+    Workspace,
+}
+
+
+/// The container engine that pulls and saves the images of the nanoservices.
+///
+/// # Variants
+/// * `Docker` - The `docker` CLI.
+/// * `Podman` - The `podman` CLI (which accepts the same `pull` and `save` commands).
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageBackend {
+    #[default]
+    Docker,
+    Podman,
+}
+
+
+impl ImageBackend {
+
+    /// The command that runs the container engine.
+    pub fn command(&self) -> &'static str {
+        match self {
+            ImageBackend::Docker => "docker",
+            ImageBackend::Podman => "podman"
+        }
+    }
+
+}
+
+
+/// When the images of the nanoservices are pulled.
+///
+/// # Variants
+/// * `Always` - The cache is wiped and every image is pulled again.
+/// * `Missing` - Only the images that are not unpacked in the cache yet are pulled.
+/// * `Never` - No images are pulled, the images have to be unpacked in the cache already.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PullPolicy {
+    #[default]
+    Always,
+    Missing,
+    Never,
+}
+
+
+/// How inconsistent nanoservice declarations are reported.
+///
+/// # Variants
+/// * `Warn` - The conflicts are printed and the process carries on.
+/// * `Error` - The process fails with the conflicts.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    #[default]
+    Warn,
+    Error,
+}
+
+
+/// Which tag of an image is chosen when the image is declared with different tags in the graph.
+///
+/// # Variants
+/// * `Highest` - The highest version is chosen.
+/// * `Lowest` - The lowest version is chosen.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnifyPolicy {
+    #[default]
+    Highest,
+    Lowest,
+}
@@ -0,0 +1,5 @@
+//! Defines the project configuration of NanoForge that is read from the `nanoforge.toml` file.
+pub mod kernel;
+pub mod read;
+pub mod profile;
+pub mod config;
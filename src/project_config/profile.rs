@@ -0,0 +1,83 @@
+//! Defines the build profile that selects which image of every nanoservice is used.
+use std::fmt;
+use std::str::FromStr;
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+
+
+/// The profile used when no profile is selected, `dev_image` is the shorthand for its image.
+pub const DEV_PROFILE: &str = "dev";
+
+/// The profile that `prod_image` is the shorthand for the image of.
+pub const PROD_PROFILE: &str = "prod";
+
+
+/// The build profile such as `dev`, `staging`, `canary`, or `prod`.
+///
+/// # Notes
+/// The name is used as a directory name in the cache so it is restricted to ASCII letters, digits, `-` and `_`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Profile(String);
+
+impl Profile {
+
+    /// The name of the profile.
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Checks if the profile is the default `dev` profile.
+    pub fn is_dev(&self) -> bool {
+        self.0 == DEV_PROFILE
+    }
+
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile(DEV_PROFILE.to_string())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = NanoServiceError;
+
+    fn from_str(profile: &str) -> Result<Self, Self::Err> {
+        let valid = !profile.is_empty() && profile.chars().all(
+            |character| character.is_ascii_alphanumeric() || character == '-' || character == '_'
+        );
+        if !valid {
+            return Err(NanoServiceError::new(
+                format!("Invalid profile `{}`, only letters, digits, `-` and `_` are allowed", profile),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        }
+        Ok(Profile(profile.to_string()))
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!("staging", "staging".parse::<Profile>().unwrap().name());
+        assert_eq!(true, "dev".parse::<Profile>().unwrap().is_dev());
+        assert_eq!(
+            "Invalid profile `../prod`, only letters, digits, `-` and `_` are allowed",
+            "../prod".parse::<Profile>().unwrap_err().message
+        );
+    }
+
+}
@@ -0,0 +1,87 @@
+//! Defines the reading of the `nanoforge.toml` file.
+use std::fs;
+use std::path::Path;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+use crate::docker_files::cache::CURRENT_WORKING_DIR;
+use crate::project_config::kernel::ProjectConfig;
+
+
+/// The name of the project configuration file.
+pub const PROJECT_CONFIG_FILE: &str = "nanoforge.toml";
+
+
+/// Reads the `nanoforge.toml` file in the current working directory.
+///
+/// # Returns
+/// The project configuration, or the default configuration if there is no `nanoforge.toml` file.
+pub fn read_project_config() -> Result<ProjectConfig, NanoServiceError> {
+    read_project_config_from(&CURRENT_WORKING_DIR.join(PROJECT_CONFIG_FILE))
+}
+
+
+/// Reads a project configuration file.
+///
+/// # Arguments
+/// * `path` - The path to the project configuration file.
+///
+/// # Returns
+/// The project configuration, or the default configuration if the file does not exist.
+pub fn read_project_config_from(path: &Path) -> Result<ProjectConfig, NanoServiceError> {
+    if !path.exists() {
+        return Ok(ProjectConfig::default())
+    }
+    let contents = safe_eject!(
+        fs::read_to_string(path),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to read project config: {}", path.display())
+    )?;
+    let config: ProjectConfig = safe_eject!(
+        toml::from_str(&contents),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to parse project config: {}", path.display())
+    )?;
+    Ok(config)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_read_project_config_vars() {
+        let config = read_project_config_from(Path::new("./tests/assets/nanoforge.toml")).unwrap();
+        let vars = config.vars.unwrap();
+
+        assert_eq!("registry.example.com", vars.get("NANO_REGISTRY").unwrap());
+    }
+
+    #[test]
+    fn test_read_project_config_profile() {
+        let config = read_project_config_from(Path::new("./tests/assets/nanoforge.toml")).unwrap();
+        assert_eq!(Some("prod".to_string()), config.profile);
+    }
+
+    #[test]
+    fn test_read_project_config_missing() {
+        let config = read_project_config_from(Path::new("./tests/assets/not_here.toml")).unwrap();
+        assert_eq!(ProjectConfig::default(), config);
+    }
+
+    #[test]
+    fn test_project_config_rejects_unknown_keys() {
+        let error = toml::from_str::<ProjectConfig>("cahce_dir = \".cache\"").unwrap_err();
+        assert_eq!(true, error.message().contains("unknown field `cahce_dir`"));
+
+        let error = toml::from_str::<ProjectConfig>("[discovery]\nexlude = [\"tests/**\"]").unwrap_err();
+        assert_eq!(true, error.message().contains("unknown field `exlude`"));
+    }
+
+}
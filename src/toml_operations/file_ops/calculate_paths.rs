@@ -13,7 +13,7 @@ use crate::docker_files::cache::process_image_name;
 /// * `cargo_path` - The path to the Cargo.toml file.
 /// * `image` - The name of the Docker image.
 /// * `entry` - The entrypoint of the nanoservice.
-/// * `nanoservices_path` - Where the nanoservices is (for cli-tool use `CACHE_NANOSERVICES_DIR.clone()`)
+/// * `nanoservices_path` - Where the nanoservices is (for cli-tool use `nanoservices_cache_dir(profile)`)
 ///
 /// # Returns
 /// The relative path to the extracted nanoservice.
@@ -0,0 +1,45 @@
+//! Defines the rendering of diffs between the contents of TOML files.
+use similar::TextDiff;
+
+
+/// Renders a unified diff between the current and the new contents of a file.
+///
+/// # Arguments
+/// * `path` - The path of the file (used in the diff header).
+/// * `current` - The current contents of the file.
+/// * `new` - The new contents of the file.
+///
+/// # Returns
+/// The unified diff, or `None` if the contents are the same.
+pub fn unified_diff(path: &str, current: &str, new: &str) -> Option<String> {
+    if current == new {
+        return None
+    }
+    Some(
+        TextDiff::from_lines(current, new)
+            .unified_diff()
+            .context_radius(3)
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string()
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let current = "[dependencies]\ntokio = \"1.0.0\"\n";
+        let new = "[dependencies]\ntokio = \"1.0.0\"\nnan-one = { path = \"../nan-one\" }\n";
+
+        assert_eq!(
+            "--- a/Cargo.toml\n+++ b/Cargo.toml\n@@ -1,2 +1,3 @@\n [dependencies]\n tokio = \"1.0.0\"\n+nan-one = { path = \"../nan-one\" }\n",
+            unified_diff("Cargo.toml", current, new).unwrap()
+        );
+        assert_eq!(None, unified_diff("Cargo.toml", current, current));
+    }
+
+}
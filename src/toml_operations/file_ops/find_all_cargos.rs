@@ -1,8 +1,18 @@
 //! Defines the actions around finding all the Cargo.toml files in the current directory.
-use walkdir::{DirEntry, WalkDir};
-use std::path::PathBuf;
+use walkdir::WalkDir;
+use ignore::WalkBuilder;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use nanoservices_utils::safe_eject;
 use nanoservices_utils::errors::{NanoServiceError, NanoServiceErrorStatus};
+use crate::docker_files::cache::nanoservices_cache_dir;
+use crate::project_config::kernel::DiscoveryMode;
+use crate::project_config::config::NanoforgeConfig;
+
+
+/// The directories that are never searched for Cargo.toml files, the cache directory of the config is never searched either.
+pub const EXCLUDED_DIRS: &[&str] = &["target", ".git", "node_modules"];
 
 
 /// The interface for finding all the Cargo.toml files in the current directory.
@@ -13,67 +23,270 @@ use nanoservices_utils::errors::{NanoServiceError, NanoServiceErrorStatus};
 /// 
 /// # Arguments
 /// * `include_cache`: A boolean value indicating if the `.nanoservices_cache` directory should be included.
+/// * `config`: The configuration with the discovery settings and the profile whose nanoservices cache is included.
 /// 
 /// # Returns
 /// A vector of all the paths to the Cargo.toml files.
-pub fn find_all_cargos_interface(include_cache: bool) -> Result<Vec<PathBuf>, NanoServiceError> {
+pub fn find_all_cargos_interface(include_cache: bool, config: &NanoforgeConfig) -> Result<Vec<PathBuf>, NanoServiceError> {
     match include_cache {
         false => {
             return safe_eject!(
-                find_all_cargos(std::env::current_dir().unwrap()),
+                find_all_cargos(std::env::current_dir().unwrap(), config),
                 NanoServiceErrorStatus::Unknown,
                 "getting all cargo paths in prep_nanoservices_once"
             )
         },
         true => {
-            return safe_eject!(
-                find_all_cargos_inc_cache(std::env::current_dir().unwrap()),
+            let base_path = std::env::current_dir().unwrap();
+            let mut paths = safe_eject!(
+                find_all_cargos(base_path.clone(), config),
                 NanoServiceErrorStatus::Unknown,
                 "getting all cargo paths in prep_nanoservices_once"
-            )
+            )?;
+            // only the nanoservices cache of the profile is searched so the other profiles are not configured
+            let cache_path = nanoservices_cache_dir(config);
+            let cache_paths = match config.discovery.mode.unwrap_or_default() {
+                DiscoveryMode::Walk => find_all_cargos_inc_cache(cache_path.clone()),
+                DiscoveryMode::Workspace => find_workspace_cargos_in_cache(cache_path.clone(), config)
+            };
+            let cache_paths = safe_eject!(
+                cache_paths,
+                NanoServiceErrorStatus::Unknown,
+                "getting all cargo paths in prep_nanoservices_once"
+            )?;
+            let relative_cache_path = cache_path.strip_prefix(&base_path).unwrap_or(&cache_path).to_path_buf();
+            paths.extend(cache_paths.into_iter().map(|path| relative_cache_path.join(path)));
+            return Ok(paths)
         }
     };
 }
 
 
-/// Checks if the entry is a Cargo.toml file.
+/// Checks if a directory is never searched for Cargo.toml files.
+///
+/// # Arguments
+/// * `name` - The name of the directory.
+///
+/// # Returns
+/// A boolean value indicating if the directory is in `EXCLUDED_DIRS`.
+fn is_excluded_dir(name: &OsStr) -> bool {
+    EXCLUDED_DIRS.iter().any(|excluded| name == *excluded)
+}
+
+
+/// Checks if a directory is the cache directory of the config.
+///
+/// # Arguments
+/// * `path` - The path to the directory relative to the current directory or absolute.
+/// * `cache_dir` - The absolute path to the cache directory.
+///
+/// # Returns
+/// `true` if the directory is the cache directory.
+fn is_cache_dir(path: &Path, cache_dir: &Path) -> bool {
+    match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(path) == cache_dir,
+        Err(_) => path == cache_dir
+    }
+}
+
+
+/// Builds a set of globs where `*` does not match a `/`, and `**` matches any number of directories.
 ///
 /// # Arguments
-/// * `entry` - A reference to the DirEntry to check.
+/// * `patterns` - The glob patterns.
 ///
 /// # Returns
-/// A boolean value indicating if the entry is a Cargo.toml file.
-fn is_cargo_toml(entry: &DirEntry) -> bool {
-    entry.file_name() == "Cargo.toml"
+/// The compiled set of globs.
+fn build_globs(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    builder.build()
 }
 
 
-/// Finds all the Cargo.toml files in the current directory and all subdirectories as long as they
-/// are not in the `.nanoservices_cache directory`.
+/// Finds all the Cargo.toml files of the project.
+///
+/// # Notes
+/// The `include` and `exclude` globs of the discovery config are matched against the path of the Cargo.toml file
+/// relative to the `base_path`, for example `services/**` or `tests/fixtures/**`, for both discovery modes.
 /// 
 /// # Arguments
 /// * `base_path`: The path in which we will perform our search of all subdirectories (can be std::env::current_dir() for cli-tool)
+/// * `config`: The configuration with the discovery mode, the include and exclude globs, and the cache directory.
 ///
 /// # Returns
 /// A vector of all the paths to the Cargo.toml files.
-pub fn find_all_cargos(base_path: PathBuf) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    let excluded_dir = base_path.join(".nanoservices_cache");
+pub fn find_all_cargos(base_path: PathBuf, config: &NanoforgeConfig) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let discovery = &config.discovery;
+    let include = match &discovery.include {
+        Some(patterns) => Some(build_globs(patterns)?),
+        None => None
+    };
+    let exclude = build_globs(discovery.exclude.as_deref().unwrap_or_default())?;
+
+    let paths = match discovery.mode.unwrap_or_default() {
+        DiscoveryMode::Walk => walk_cargos(&base_path, config),
+        DiscoveryMode::Workspace => find_workspace_cargos(&base_path, config)?
+    };
+    Ok(paths.into_iter()
+        .filter(|path| include.as_ref().map(|include| include.is_match(path)).unwrap_or(true))
+        .filter(|path| !exclude.is_match(path))
+        .collect())
+}
 
+
+/// Walks the directory tree to find all the Cargo.toml files in a directory and all subdirectories.
+///
+/// # Notes
+/// The `.gitignore` and `.ignore` files are respected, and the directories in `EXCLUDED_DIRS` and the cache directory
+/// are never searched.
+/// 
+/// # Arguments
+/// * `base_path`: The path in which we will perform our search of all subdirectories.
+/// * `config`: The configuration with the cache directory.
+///
+/// # Returns
+/// A vector of all the paths to the Cargo.toml files relative to the `base_path`.
+fn walk_cargos(base_path: &Path, config: &NanoforgeConfig) -> Vec<PathBuf> {
+    let cache_dir = config.cache_dir.clone();
+    WalkBuilder::new(base_path)
+        // hidden directories can hold Cargo.toml files, the ones that should not be searched are in EXCLUDED_DIRS
+        .hidden(false)
+        .filter_entry(move |entry| !is_excluded_dir(entry.file_name()) && !is_cache_dir(entry.path(), &cache_dir))
+        .sort_by_file_name(|one, two| one.cmp(two))
+        .build()
+        .filter_map(Result::ok) // Ignore any errors during iteration
+        .filter(|e| e.file_name() == "Cargo.toml")
+        .map(|entry| entry.path().strip_prefix(base_path).unwrap().to_path_buf())
+        .collect()
+}
+
+
+/// Gets the paths in the `members` or `exclude` array of a `[workspace]` table.
+///
+/// # Notes
+/// Cargo accepts `./crates/one` and `crates/one/` so these are normalised to `crates/one` for matching.
+///
+/// # Arguments
+/// * `workspace` - The `[workspace]` table.
+/// * `key` - The key of the array.
+///
+/// # Returns
+/// The normalised paths (anything that is not a string is ignored).
+fn workspace_paths(workspace: &toml::Value, key: &str) -> Vec<String> {
+    workspace.get(key)
+        .and_then(|paths| paths.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|path| path.as_str())
+        .map(|path| path.trim_start_matches("./").trim_end_matches('/').to_string())
+        .collect()
+}
+
+
+/// Finds the Cargo.toml files of the crates in the workspace of the Cargo.toml file in a directory.
+///
+/// # Notes
+/// The root Cargo.toml file is only returned if it has a `[package]` as a virtual manifest cannot have
+/// dependencies. The `members` of the `[workspace]` are globs of the directories of the member crates, and the
+/// `exclude` entries are directories that are left out with all the directories in them. The cache directory is
+/// never searched.
+///
+/// # Arguments
+/// * `base_path`: The directory of the root Cargo.toml file.
+/// * `config`: The configuration with the cache directory.
+///
+/// # Returns
+/// A vector of all the paths to the Cargo.toml files relative to the `base_path`.
+pub fn find_workspace_cargos(base_path: &Path, config: &NanoforgeConfig) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(base_path.join("Cargo.toml"))?)?;
     let mut paths = Vec::new();
 
-    for entry in WalkDir::new(&base_path)
+    if manifest.get("package").is_some() {
+        paths.push(PathBuf::from("Cargo.toml"));
+    }
+    let workspace = match manifest.get("workspace") {
+        Some(workspace) => workspace,
+        None => return Ok(paths)
+    };
+    let members = build_globs(&workspace_paths(workspace, "members"))?;
+    let excluded = workspace_paths(workspace, "exclude");
+
+    for entry in WalkDir::new(base_path)
+        .sort_by_file_name()
         .into_iter()
+        .filter_entry(|e| e.depth() == 0 || (!is_excluded_dir(e.file_name()) && !is_cache_dir(e.path(), &config.cache_dir)))
         .filter_map(Result::ok) // Ignore any errors during iteration
-        .filter(|e| !e.path().starts_with(&excluded_dir) && is_cargo_toml(e)) // Exclude specified directory and check for Cargo.toml
+        .filter(|e| e.depth() > 1 && e.file_name() == "Cargo.toml")
     {
-        let relative_path = entry.path().strip_prefix(&base_path).unwrap();
-        paths.push(PathBuf::from(relative_path));
+        let relative_path = entry.path().strip_prefix(base_path).unwrap();
+        // unwrap is safe as the depth is more than one so the path has a parent
+        let member_path = relative_path.parent().unwrap();
+        if members.is_match(member_path) && !excluded.iter().any(|path| member_path.starts_with(path)) {
+            paths.push(relative_path.to_path_buf());
+        }
+    }
+    Ok(paths)
+}
+
+
+/// Finds the Cargo.toml files in the directory of an unpacked nanoservice.
+///
+/// # Notes
+/// In the workspace mode a nanoservice without a Cargo.toml file at the root of its image is searched by walking
+/// its directory tree.
+///
+/// # Arguments
+/// * `nanoservice_path`: The directory that the image of the nanoservice is unpacked into.
+/// * `config`: The configuration with the discovery mode that decides how the Cargo.toml files are found.
+///
+/// # Returns
+/// A vector of all the paths to the Cargo.toml files relative to the `nanoservice_path`.
+pub fn find_nanoservice_cargos(nanoservice_path: PathBuf, config: &NanoforgeConfig) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    match config.discovery.mode.unwrap_or_default() {
+        DiscoveryMode::Workspace if nanoservice_path.join("Cargo.toml").exists() => find_workspace_cargos(&nanoservice_path, config),
+        _ => find_all_cargos_inc_cache(nanoservice_path)
+    }
+}
+
+
+/// Finds the Cargo.toml files of the workspaces of the nanoservices unpacked in a nanoservices cache directory.
+///
+/// # Arguments
+/// * `base_path`: The nanoservices cache directory.
+/// * `config`: The configuration in the workspace discovery mode.
+///
+/// # Returns
+/// A vector of all the paths to the Cargo.toml files relative to the `base_path`.
+pub fn find_workspace_cargos_in_cache(base_path: PathBuf, config: &NanoforgeConfig) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    if !base_path.exists() {
+        return Ok(paths)
+    }
+    let mut nanoservices: Vec<PathBuf> = std::fs::read_dir(&base_path)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    nanoservices.sort();
+
+    for nanoservice in nanoservices {
+        let cargo_paths = find_nanoservice_cargos(nanoservice.clone(), config)?;
+        // unwrap is safe as the nanoservice was read from the base path
+        let name = nanoservice.strip_prefix(&base_path).unwrap();
+        paths.extend(cargo_paths.into_iter().map(|path| name.join(path)));
     }
     Ok(paths)
 }
 
 
-/// Finds all the Cargo.toml files in the current directory and all subdirectories including the `.nanoservices_cache directory`.
+/// Finds all the Cargo.toml files in a directory of the `.nanoservices_cache` directory and all its subdirectories.
+///
+/// # Notes
+/// The ignore files and the discovery globs are not applied as the cache is generated by NanoForge, but the
+/// `target`, `.git`, and `node_modules` directories of the unpacked images are still skipped.
 /// 
 /// # Arguments
 /// * `base_path`: The path in which we will perform our search of all subdirectories (can be std::env::current_dir() for cli-tool)
@@ -84,9 +297,11 @@ pub fn find_all_cargos_inc_cache(base_path: PathBuf) -> Result<Vec<PathBuf>, Box
     let mut paths = Vec::new();
 
     for entry in WalkDir::new(&base_path)
+        .sort_by_file_name()
         .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !is_excluded_dir(e.file_name()))
         .filter_map(Result::ok) // Ignore any errors during iteration
-        .filter(|e| is_cargo_toml(e)) // Exclude specified directory and check for Cargo.toml
+        .filter(|e| e.file_name() == "Cargo.toml")
     {
         let relative_path = entry.path().strip_prefix(&base_path).unwrap();
         paths.push(PathBuf::from(relative_path));
@@ -99,6 +314,16 @@ pub fn find_all_cargos_inc_cache(base_path: PathBuf) -> Result<Vec<PathBuf>, Box
 mod tests {
 
     use super::*;
+    use crate::project_config::kernel::DiscoveryConfig;
+
+    /// The config with the cache of the fixture in the `base_path` as if NanoForge was run in the `base_path`.
+    fn discovery_config(base_path: &str, discovery: DiscoveryConfig) -> NanoforgeConfig {
+        NanoforgeConfig {
+            discovery,
+            cache_dir: std::env::current_dir().unwrap().join(base_path).join(".nanoservices_cache"),
+            ..NanoforgeConfig::default()
+        }
+    }
 
     #[test]
     fn test_find_all_cargos_at_base() {
@@ -111,7 +336,7 @@ mod tests {
 
         assert_eq!(
             expected_path,
-            find_all_cargos(base_path).unwrap()
+            find_all_cargos(base_path, &discovery_config("tests/path_testing", DiscoveryConfig::default())).unwrap()
         );
     }
 
@@ -126,8 +351,96 @@ mod tests {
         
         assert_eq!(
             expected_path,
-            find_all_cargos(base_path).unwrap()
+            find_all_cargos(base_path, &discovery_config("tests/path_testing/one", DiscoveryConfig::default())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_find_all_cargos_skips_cache_dir() {
+        // the default cache directory is searched when another cache directory is configured
+        let config = NanoforgeConfig {
+            cache_dir: std::env::current_dir().unwrap().join("tests/path_testing/one"),
+            ..NanoforgeConfig::default()
+        };
+        assert_eq!(
+            vec![
+                PathBuf::from(".nanoservices_cache/one/Cargo.toml"),
+                PathBuf::from(".nanoservices_cache/one/three/Cargo.toml"),
+                PathBuf::from(".nanoservices_cache/two/Cargo.toml"),
+                PathBuf::from("two/Cargo.toml")
+            ],
+            find_all_cargos(PathBuf::from("./tests/path_testing"), &config).unwrap()
         );
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_find_all_cargos_with_globs() {
+        let base_path = PathBuf::from("./tests/path_testing");
+
+        let discovery = DiscoveryConfig {
+            include: None,
+            exclude: Some(vec!["one/three/**".to_string()]),
+            mode: None
+        };
+        assert_eq!(
+            vec![PathBuf::from("one/Cargo.toml"), PathBuf::from("two/Cargo.toml")],
+            find_all_cargos(base_path.clone(), &discovery_config("tests/path_testing", discovery)).unwrap()
+        );
+
+        let discovery = DiscoveryConfig {
+            include: Some(vec!["one/*".to_string()]),
+            exclude: None,
+            mode: None
+        };
+        assert_eq!(
+            vec![PathBuf::from("one/Cargo.toml")],
+            find_all_cargos(base_path, &discovery_config("tests/path_testing", discovery)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_find_all_cargos_invalid_glob() {
+        let discovery = DiscoveryConfig {
+            include: None,
+            exclude: Some(vec!["one/[".to_string()]),
+            mode: None
+        };
+        assert!(find_all_cargos(PathBuf::from("./tests/path_testing"), &discovery_config("tests/path_testing", discovery)).is_err());
+    }
+
+    #[test]
+    fn test_find_all_cargos_workspace_mode() {
+        let discovery = DiscoveryConfig {
+            include: None,
+            exclude: None,
+            mode: Some(DiscoveryMode::Workspace)
+        };
+        let expected_path = vec![
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("crates/one/Cargo.toml"),
+            PathBuf::from("tools/cli/Cargo.toml")
+        ];
+
+        assert_eq!(
+            expected_path,
+            find_all_cargos(PathBuf::from("./tests/workspace_testing"), &discovery_config("tests/workspace_testing", discovery)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_find_workspace_cargos_in_cache() {
+        let expected_path = vec![
+            PathBuf::from("nan-one/kernel/Cargo.toml"),
+            PathBuf::from("nan-two/Cargo.toml")
+        ];
+
+        assert_eq!(
+            expected_path,
+            find_workspace_cargos_in_cache(
+                PathBuf::from("./tests/workspace_testing/.nanoservices_cache"),
+                &discovery_config("tests/workspace_testing", DiscoveryConfig { include: None, exclude: None, mode: Some(DiscoveryMode::Workspace) })
+            ).unwrap()
+        );
+    }
+
+}
@@ -2,3 +2,4 @@ pub mod calculate_paths;
 pub mod find_all_cargos;
 pub mod read;
 pub mod write;
+pub mod diff;
@@ -1,5 +1,6 @@
 //! Defines the functionality around reading TOML files.
 use std::fs;
+use std::path::Path;
 use nanoservices_utils::{
     safe_eject,
     errors::{
@@ -17,16 +18,16 @@ use crate::toml_operations::kernel::CargoToml;
 ///
 /// # Returns
 /// A CargoToml struct representing the parsed Cargo.toml file.
-pub fn read_toml(cargo_toml_path: &str) -> Result<CargoToml, NanoServiceError> {
+pub fn read_toml(cargo_toml_path: &Path) -> Result<CargoToml, NanoServiceError> {
     let cargo_toml_contents = safe_eject!(
         fs::read_to_string(cargo_toml_path),
         NanoServiceErrorStatus::Unknown,
-        format!("Failed to read Cargo.toml: {}", cargo_toml_path)
+        format!("Failed to read Cargo.toml: {}", cargo_toml_path.display())
     )?;
     let cargo_toml: CargoToml = safe_eject!(
         toml::from_str(&cargo_toml_contents),
         NanoServiceErrorStatus::Unknown,
-        format!("Failed to parse Cargo.toml: {}", cargo_toml_path)
+        format!("Failed to parse Cargo.toml: {}", cargo_toml_path.display())
     )?;
     Ok(cargo_toml)
 }
@@ -37,6 +38,7 @@ mod tests {
 
     use super::*;
     use crate::toml_operations::kernel::Package;
+    use toml::Table;
 
     #[test]
     fn test_read_success_package() {
@@ -44,10 +46,12 @@ mod tests {
         let expected_package = Package {
             version: "0.1.0".to_string(),
             name: "testing_two".to_string(),
-            edition: "2021".to_string()
+            edition: "2021".to_string(),
+            metadata: None,
+            other: Table::new()
         };
 
-        let cargo_data = read_toml("./tests/assets/Cargo.toml").unwrap();
+        let cargo_data = read_toml(Path::new("./tests/assets/Cargo.toml")).unwrap();
 
         let read_package = cargo_data.package.unwrap();
 
@@ -60,7 +64,7 @@ mod tests {
 
     #[test]
     fn test_read_success_dependencies() {
-        let cargo_data = read_toml("./tests/assets/Cargo.toml").unwrap();
+        let cargo_data = read_toml(Path::new("./tests/assets/Cargo.toml")).unwrap();
         let read_deps = cargo_data.dependencies.unwrap();
 
         assert_eq!(
@@ -71,7 +75,7 @@ mod tests {
 
     #[test]
     fn test_read_success_nanoservices() {
-        let cargo_data = read_toml("./tests/assets/Cargo.toml").unwrap();
+        let cargo_data = read_toml(Path::new("./tests/assets/Cargo.toml")).unwrap();
         let read_nanoservices = cargo_data.nanoservices.unwrap();
 
         let nan_one = read_nanoservices.get("nan-one").unwrap();
@@ -80,11 +84,11 @@ mod tests {
 
         // testing nan-one
         assert_eq!(
-            "maxwellflitton/nan-one".to_string(),
+            Some("maxwellflitton/nan-one".to_string()),
             nan_one.dev_image
         );
         assert_eq!(
-            "maxwellflitton/nan-one".to_string(),
+            Some("maxwellflitton/nan-one".to_string()),
             nan_one.prod_image
         );
         assert_eq!(
@@ -102,11 +106,11 @@ mod tests {
 
         // testing nan-two
         assert_eq!(
-            "maxwellflitton/nan-two".to_string(),
+            Some("maxwellflitton/nan-two".to_string()),
             nan_two.dev_image
         );
         assert_eq!(
-            "maxwellflitton/nan-two".to_string(),
+            Some("maxwellflitton/nan-two".to_string()),
             nan_two.prod_image
         );
         assert_eq!(
@@ -126,7 +130,7 @@ mod tests {
     #[test]
     fn test_read_fail_corrupt() {
 
-        match read_toml("./tests/assets/corrupt.toml") {
+        match read_toml(Path::new("./tests/assets/corrupt.toml")) {
             Ok(_) => {
                 // should fail
                 assert_eq!(1, 2);
@@ -145,7 +149,7 @@ mod tests {
 
     #[test]
     fn test_read_nothing() {
-        match read_toml("./tests/assets/nothing.toml") {
+        match read_toml(Path::new("./tests/assets/nothing.toml")) {
             Ok(data) => {
                 assert_eq!(
                     data.dependencies,
@@ -169,7 +173,7 @@ mod tests {
 
     #[test]
     fn test_read_fail_missing() {
-        match read_toml("./tests/assets/not_here.toml") {
+        match read_toml(Path::new("./tests/assets/not_here.toml")) {
             Ok(_) => {
                 assert_eq!(1, 2);
             },
@@ -1,5 +1,6 @@
 //! Defines the functionality around the reading and writing of TOML files.
 use std::fs;
+use std::path::Path;
 use nanoservices_utils::{
     safe_eject,
     errors::{
@@ -10,6 +11,23 @@ use nanoservices_utils::{
 use crate::toml_operations::kernel::RawCargoToml;
 
 
+/// Serializes a CargoToml struct into the string that is written to a Cargo.toml file.
+///
+/// # Arguments
+/// * `cargo_toml_path` - The path to the Cargo.toml file (used for error messages).
+/// * `cargo_toml` - The CargoToml struct to serialize.
+///
+/// # Returns
+/// The serialized contents of the Cargo.toml file.
+pub fn serialize_toml(cargo_toml_path: &Path, cargo_toml: &RawCargoToml) -> Result<String, NanoServiceError> {
+    safe_eject!(
+        toml::to_string(cargo_toml),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to serialize Cargo.toml for writing: {}", cargo_toml_path.display())
+    )
+}
+
+
 /// Writes a CargoToml struct to a Cargo.toml file.
 ///
 /// # Arguments
@@ -18,16 +36,12 @@ use crate::toml_operations::kernel::RawCargoToml;
 ///
 /// # Returns
 /// None
-pub fn write_toml(cargo_toml_path: &str, cargo_toml: RawCargoToml) -> Result<(), NanoServiceError> {
-    let modified_toml = safe_eject!(
-        toml::to_string(&cargo_toml),
-        NanoServiceErrorStatus::Unknown,
-        format!("Failed to serialize Cargo.toml for writing: {}", cargo_toml_path)
-    )?;
+pub fn write_toml(cargo_toml_path: &Path, cargo_toml: RawCargoToml) -> Result<(), NanoServiceError> {
+    let modified_toml = serialize_toml(cargo_toml_path, &cargo_toml)?;
     safe_eject!(
         fs::write(cargo_toml_path, modified_toml),
         NanoServiceErrorStatus::Unknown,
-        format!("Failed to write Cargo.toml: {}", cargo_toml_path)
+        format!("Failed to write Cargo.toml: {}", cargo_toml_path.display())
     )?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use toml::Value;
+use std::collections::{BTreeMap, HashMap};
+use toml::{Table, Value};
 use crate::toml_operations::nanoservices::kernel::Nanoservice;
 
 
@@ -10,11 +10,16 @@ use crate::toml_operations::nanoservices::kernel::Nanoservice;
 /// * `name` - The name of the package.
 /// * `version` - The version of the package.
 /// * `edition` - The edition of the package.
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+/// * `metadata` - The metadata table of the package, NanoForge records its managed dependencies under `nanoforge`.
+/// * `other` - All the other fields of the package that are passed through untouched.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Package {
     pub name: String,
     pub version: String,
     pub edition: String,
+    pub metadata: Option<Table>,
+    #[serde(flatten)]
+    pub other: Table,
 }
 
 
@@ -26,11 +31,16 @@ pub struct Package {
 /// * `package` - The package section of the Cargo.toml file.
 /// * `dependencies` - The dependencies section of the Cargo.toml file.
 /// * `nanoservices` - The nanoservices section of the Cargo.toml file.
+/// * `features` - The features section of the Cargo.toml file.
+/// * `other` - All the other sections of the Cargo.toml file that are passed through untouched.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CargoToml {
     pub package: Option<Package>,
     pub dependencies: Option<HashMap<String, Value>>,
     pub nanoservices: Option<HashMap<String, Nanoservice>>,
+    pub features: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(flatten)]
+    pub other: Table,
 }
 
 impl CargoToml {
@@ -43,7 +53,9 @@ impl CargoToml {
         let package = self.package?;
         let dependencies = self.dependencies?;
         let nanoservices = self.nanoservices;
-        Some(RawCargoToml { package, dependencies, nanoservices })
+        let features = self.features;
+        let other = self.other;
+        Some(RawCargoToml { package, dependencies, nanoservices, features, other })
     }
 
 }
@@ -51,9 +63,99 @@ impl CargoToml {
 
 /// Represents the structure of a Cargo.toml file for file saving and manipulation
 /// of the cargo file in relation to configuring of nanoservices and dependencies.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct RawCargoToml {
     pub package: Package,
     pub dependencies: HashMap<String, Value>,
     pub nanoservices: Option<HashMap<String, Nanoservice>>,
+    pub features: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(flatten)]
+    pub other: Table,
+}
+
+
+#[cfg(test)]
+impl RawCargoToml {
+
+    /// Builds a Cargo.toml file for the tests with only a package.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the package.
+    ///
+    /// # Returns
+    /// The Cargo.toml file without dependencies, nanoservices, features, or metadata.
+    pub fn fixture(name: &str) -> RawCargoToml {
+        RawCargoToml {
+            package: Package {
+                name: name.to_string(),
+                version: "0.1.0".to_string(),
+                edition: "2021".to_string(),
+                metadata: None,
+                other: Table::new()
+            },
+            dependencies: HashMap::new(),
+            nanoservices: None,
+            features: None,
+            other: Table::new()
+        }
+    }
+
+}
+
+
+impl RawCargoToml {
+
+    /// Adds a `dep:` entry for a dependency to a feature, creating the feature if it does not exist.
+    ///
+    /// # Arguments
+    /// * `feature` - The name of the feature that enables the dependency.
+    /// * `dependency` - The name of the optional dependency.
+    ///
+    /// # Returns
+    /// `true` if the feature was created.
+    pub fn enable_dependency_in_feature(&mut self, feature: &str, dependency: &str) -> bool {
+        let entry = format!("dep:{}", dependency);
+        let features = self.features.get_or_insert_with(BTreeMap::new);
+        let created = !features.contains_key(feature);
+        let enabled = features.entry(feature.to_string()).or_default();
+        if !enabled.contains(&entry) {
+            enabled.push(entry);
+        }
+        created
+    }
+
+    /// Removes the `dep:` entries of a dependency from all the features.
+    ///
+    /// # Notes
+    /// The features themselves are kept even if they end up empty, the features created by NanoForge are removed
+    /// with `remove_empty_features`.
+    ///
+    /// # Arguments
+    /// * `dependency` - The name of the dependency being removed.
+    pub fn remove_dependency_from_features(&mut self, dependency: &str) {
+        let entry = format!("dep:{}", dependency);
+        if let Some(features) = self.features.as_mut() {
+            for enabled in features.values_mut() {
+                enabled.retain(|item| item != &entry);
+            }
+        }
+    }
+
+    /// Removes features that are empty.
+    ///
+    /// # Notes
+    /// The features table is removed too if removing the features leaves it empty.
+    ///
+    /// # Arguments
+    /// * `names` - The names of the features to remove if they are empty.
+    pub fn remove_empty_features(&mut self, names: &[String]) {
+        if let Some(features) = self.features.as_mut() {
+            let count = features.len();
+            features.retain(|name, enabled| !(enabled.is_empty() && names.contains(name)));
+            if features.is_empty() && features.len() != count {
+                self.features = None;
+            }
+        }
+    }
+
 }
@@ -1,5 +1,4 @@
 //! Defines the actions around reading, writing, and configuring TOML files.
-pub mod read;
 pub mod kernel;
 pub mod nanoservices;
 pub mod domainservices;
@@ -1,4 +1,4 @@
-use crate::toml_operations::nanoservices::kernel::Nanoservice;
+use crate::toml_operations::nanoservices::kernel::{Nanoservice, DependencyEntry};
 use toml::{Table, Value};
 use crate::toml_operations::kernel::RawCargoToml;
 use nanoservices_utils::{
@@ -9,6 +9,8 @@ use nanoservices_utils::{
     }
 };
 use crate::toml_operations::file_ops::calculate_paths::calculate_relative_path;
+use crate::toml_operations::nanoservices::ownership::{record_managed_dependencies, record_managed_features};
+use crate::project_config::profile::Profile;
 use std::path::PathBuf;
 
 // pub type CargoDependencies = HashMap<std::path::PathBuf, Vec<(String, Nanoservice)>>;
@@ -18,9 +20,14 @@ use std::path::PathBuf;
 /// Configures a Cargo.toml file with the nanoservices relative path and adds this relative path
 /// to the dependencies section so the nanoservice can be built into the project.
 ///
+/// # Notes
+/// Every dependency inserted, and every feature created to gate them, is recorded in the
+/// `[package.metadata.nanoforge]` ownership record so it can be wiped in the next cycle.
+///
 /// # Arguments
 /// * `path` - The path to the Cargo.toml file to configure.
 /// * `nanos` - A vector of tuples containing the name of the nanoservice and the Nanoservice struct.
+/// * `profile` - The active profile that selects the image of each nanoservice.
 ///
 /// # Returns
 /// None
@@ -28,15 +35,19 @@ pub fn config_cargo(
         mut cargo_toml: RawCargoToml, 
         nanos:  Vec<(String, Nanoservice)>, 
         nanoservices_path: PathBuf,
-        cargo_toml_path: PathBuf
+        cargo_toml_path: PathBuf,
+        profile: &Profile
     ) -> Result<RawCargoToml, NanoServiceError> {
+    let mut managed = Vec::new();
+    let mut created_features = Vec::new();
+
     // loop through nanos and add them to the dependencies section as tables
     for (name, nanoservice) in nanos {
         let mut nanoservice_table = Table::new();
         let relative_path = safe_eject!(
             calculate_relative_path(
                 &cargo_toml_path,
-                &nanoservice.dev_image,
+                nanoservice.image(profile)?,
                 &nanoservice.entrypoint,
                 &nanoservices_path
             ),
@@ -58,23 +69,271 @@ pub fn config_cargo(
             "path".to_string(),
             Value::String(relative_path_str)
         );
-        // add optional features
-        nanoservice.add_features(&mut nanoservice_table);
-        nanoservice.add_package(&mut nanoservice_table);
-        
+        // add optional keys
+        nanoservice.add_dependency_keys(&mut nanoservice_table);
+
+        // gate the nanoservice behind the declared Cargo feature
+        if let Some(feature) = &nanoservice.feature {
+            if cargo_toml.enable_dependency_in_feature(feature, &name) {
+                created_features.push(feature.clone());
+            }
+        }
+
         // insert the contructed nanoervice table into the dependencies section of the Cargo.toml
+        managed.push(name.clone());
         cargo_toml.dependencies.insert(name, toml::Value::Table(nanoservice_table));
 
-        // insert the kernel of the nanoservice into the dependencies section of the Cargo.toml if exists
-        let nanoservice_kernel = match nanoservice.construct_kernel(&cargo_toml_path, &nanoservices_path)? {
-            Some(kernel) => kernel,
-            None => continue
-        };
-        // can directly unwrap here as the kernel is guaranteed to exist otherwise the `nanoservice_kernel` would not have
-        // been constructed
-        let name = nanoservice.kernel.unwrap().name.clone();
-        cargo_toml.dependencies.insert(name, toml::Value::Table(nanoservice_kernel));
-
+        // insert the kernels of the nanoservice into the dependencies section of the Cargo.toml
+        for (kernel_name, mut kernel_table) in nanoservice.construct_kernels(&cargo_toml_path, &nanoservices_path, profile)? {
+            // the kernels of a gated nanoservice are enabled by the same feature
+            if let Some(feature) = &nanoservice.feature {
+                kernel_table.insert("optional".to_string(), Value::Boolean(true));
+                if cargo_toml.enable_dependency_in_feature(feature, &kernel_name) {
+                    created_features.push(feature.clone());
+                }
+            }
+            managed.push(kernel_name.clone());
+            cargo_toml.dependencies.insert(kernel_name, toml::Value::Table(kernel_table));
+        }
     }
+    record_managed_dependencies(&mut cargo_toml, managed);
+    record_managed_features(&mut cargo_toml, created_features);
     Ok(cargo_toml)
 }
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::ownership::{get_managed_dependencies, get_managed_features};
+    use crate::toml_operations::nanoservices::kernel::{ExtraKeys, NanoserviceKernel};
+
+    #[test]
+    fn test_config_cargo_optional_with_feature() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.feature = Some("with-nan-one".to_string());
+        let nanos = vec![("nan-one".to_string(), nanoservice)];
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            nanos,
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        let dependency = cargo_toml.dependencies.get("nan-one").unwrap();
+        assert_eq!(
+            ".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/.",
+            dependency.get("path").unwrap().as_str().unwrap()
+        );
+        assert_eq!(true, dependency.get("optional").unwrap().as_bool().unwrap());
+        assert_eq!(
+            &vec!["dep:nan-one".to_string()],
+            cargo_toml.features.as_ref().unwrap().get("with-nan-one").unwrap()
+        );
+        assert_eq!(
+            vec!["nan-one".to_string()],
+            get_managed_dependencies(&cargo_toml)
+        );
+        assert_eq!(vec!["with-nan-one".to_string()], get_managed_features(&cargo_toml));
+    }
+
+    #[test]
+    fn test_config_cargo_feature_declared_by_user() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.features = Some(std::collections::BTreeMap::from([("with-nan-one".to_string(), Vec::new())]));
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.feature = Some("with-nan-one".to_string());
+
+        let cargo_toml = config_cargo(
+            cargo_toml,
+            vec![("nan-one".to_string(), nanoservice)],
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        assert_eq!(Vec::<String>::new(), get_managed_features(&cargo_toml));
+    }
+
+    #[test]
+    fn test_config_cargo_feature_with_kernels() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.feature = Some("with-nan-one".to_string());
+        nanoservice.kernel = Some(NanoserviceKernel::fixture("nan-one-kernel", "kernel"));
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            vec![("nan-one".to_string(), nanoservice)],
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        let kernel = cargo_toml.dependencies.get("nan-one-kernel").unwrap();
+        assert_eq!(true, kernel.get("optional").unwrap().as_bool().unwrap());
+        assert_eq!(
+            &vec!["dep:nan-one".to_string(), "dep:nan-one-kernel".to_string()],
+            cargo_toml.features.as_ref().unwrap().get("with-nan-one").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_cargo_not_optional() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.optional = Some(false);
+        let nanos = vec![("nan-one".to_string(), nanoservice)];
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            nanos,
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        let dependency = cargo_toml.dependencies.get("nan-one").unwrap();
+        assert_eq!(None, dependency.get("optional"));
+        assert_eq!(None, cargo_toml.features);
+    }
+
+    #[test]
+    fn test_config_cargo_dependency_keys() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut extra = Table::new();
+        extra.insert("registry".to_string(), Value::String("internal".to_string()));
+        extra.insert("path".to_string(), Value::String("../somewhere-else".to_string()));
+
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.default_features = Some(false);
+        nanoservice.extra = Some(ExtraKeys(extra.clone()));
+        nanoservice.kernel = Some(NanoserviceKernel {
+            features: Some(vec!["types".to_string()]),
+            default_features: Some(false),
+            extra: Some(ExtraKeys(extra)),
+            ..NanoserviceKernel::fixture("nan-one-kernel", "kernel")
+        });
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            vec![("nan-one".to_string(), nanoservice)],
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        let dependency = cargo_toml.dependencies.get("nan-one").unwrap();
+        assert_eq!(false, dependency.get("default-features").unwrap().as_bool().unwrap());
+        assert_eq!("internal", dependency.get("registry").unwrap().as_str().unwrap());
+        assert_eq!(
+            ".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/.",
+            dependency.get("path").unwrap().as_str().unwrap()
+        );
+
+        let kernel = cargo_toml.dependencies.get("nan-one-kernel").unwrap();
+        assert_eq!(false, kernel.get("default-features").unwrap().as_bool().unwrap());
+        assert_eq!("internal", kernel.get("registry").unwrap().as_str().unwrap());
+        assert_eq!(
+            ".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/kernel",
+            kernel.get("path").unwrap().as_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_cargo_multiple_kernels() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.kernel = Some(NanoserviceKernel::fixture("nan-one-kernel", "kernel"));
+        nanoservice.kernels = Some(vec![
+            NanoserviceKernel::fixture("nan-one-types", "types"),
+            NanoserviceKernel::fixture("nan-one-client", "client"),
+        ]);
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            vec![("nan-one".to_string(), nanoservice)],
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        for (name, entrypoint) in [("nan-one-kernel", "kernel"), ("nan-one-types", "types"), ("nan-one-client", "client")] {
+            assert_eq!(
+                format!(".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/{}", entrypoint),
+                cargo_toml.dependencies.get(name).unwrap().get("path").unwrap().as_str().unwrap()
+            );
+        }
+        assert_eq!(
+            vec![
+                "nan-one".to_string(),
+                "nan-one-client".to_string(),
+                "nan-one-kernel".to_string(),
+                "nan-one-types".to_string()
+            ],
+            get_managed_dependencies(&cargo_toml)
+        );
+    }
+
+    #[test]
+    fn test_config_cargo_kernel_image() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/domain_services/nanoservices");
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.kernel = Some(NanoserviceKernel {
+            image: Some("maxwellflitton/nan-one-kernel".to_string()),
+            ..NanoserviceKernel::fixture("nan-one-kernel", ".")
+        });
+        assert_eq!(
+            vec!["maxwellflitton/nan-one", "maxwellflitton/nan-one-kernel"],
+            nanoservice.images(&Profile::default()).unwrap()
+        );
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            vec![("nan-one".to_string(), nanoservice)],
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &Profile::default()
+        ).unwrap();
+
+        let kernel = cargo_toml.dependencies.get("nan-one-kernel").unwrap();
+        assert_eq!(
+            ".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one-kernel/.",
+            kernel.get("path").unwrap().as_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_cargo_prod_profile() {
+        let nanoservices_path = std::env::current_dir().unwrap()
+            .join(".nanoservices_cache/profiles/prod/nanoservices");
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.prod_image = Some("maxwellflitton/nan-one-hardened".to_string());
+
+        let cargo_toml = config_cargo(
+            RawCargoToml::fixture("test"),
+            vec![("nan-one".to_string(), nanoservice)],
+            nanoservices_path,
+            PathBuf::from("Cargo.toml"),
+            &"prod".parse().unwrap()
+        ).unwrap();
+
+        assert_eq!(
+            ".nanoservices_cache/profiles/prod/nanoservices/maxwellflitton_nan-one-hardened/.",
+            cargo_toml.dependencies.get("nan-one").unwrap().get("path").unwrap().as_str().unwrap()
+        );
+    }
+
+}
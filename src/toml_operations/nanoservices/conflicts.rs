@@ -0,0 +1,253 @@
+//! Defines the analysis of inconsistent nanoservice declarations across the `Cargo.toml` files.
+//!
+//! # Notes
+//! A nanoservice name declared with different images, entrypoints, or kernels in different `Cargo.toml` files, or one
+//! image declared under different names with different entrypoints, builds different crates from what looks like one
+//! nanoservice. Depending on the `conflicts` policy these are printed as warnings or fail the process.
+//!
+//! The images are compared without their tags. The conflicts are checked after the unification, which settles
+//! the images declared with different tags, so a difference in tags alone is never a conflict.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+use crate::docker_files::registry::ImageReference;
+use crate::project_config::config::NanoforgeConfig;
+use crate::project_config::kernel::ConflictPolicy;
+use crate::toml_operations::nanoservices::kernel::Nanoservice;
+use crate::toml_operations::nanoservices::resolver::NanoserviceGraph;
+
+
+/// An inconsistency between nanoservice declarations.
+///
+/// # Fields
+/// * `message` - What is inconsistent and which `Cargo.toml` files declare each value.
+/// * `manifests` - The paths to all the `Cargo.toml` files involved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub message: String,
+    pub manifests: Vec<PathBuf>,
+}
+
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+
+/// A declaration of a nanoservice in a `Cargo.toml` file.
+struct Declaration<'a> {
+    manifest: &'a PathBuf,
+    name: &'a String,
+    nanoservice: &'a Nanoservice,
+}
+
+
+/// Gets the value of a declaration that is compared across the `Cargo.toml` files.
+type DeclarationValue<'a> = &'a dyn Fn(&Declaration) -> String;
+
+
+/// Describes the distinct values of the declarations and the `Cargo.toml` files declaring them.
+///
+/// # Arguments
+/// * `declarations` - The declarations to compare.
+/// * `value` - Gets the compared value of a declaration.
+///
+/// # Returns
+/// A description such as "`a` (one/Cargo.toml), `b` (two/Cargo.toml)" if there is more than one distinct value.
+fn describe_differences(declarations: &[Declaration], value: impl Fn(&Declaration) -> String) -> Option<String> {
+    let mut values: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for declaration in declarations {
+        let manifests = values.entry(value(declaration)).or_default();
+        let manifest = declaration.manifest.display().to_string();
+        if !manifests.contains(&manifest) {
+            manifests.push(manifest);
+        }
+    }
+    if values.len() < 2 {
+        return None
+    }
+    Some(values.into_iter()
+        .map(|(value, manifests)| format!("`{}` ({})", value, manifests.join(", ")))
+        .collect::<Vec<String>>()
+        .join(", "))
+}
+
+
+/// Gets the paths to the `Cargo.toml` files of the declarations without duplicates.
+fn manifests_of(declarations: &[Declaration]) -> Vec<PathBuf> {
+    let mut manifests: Vec<PathBuf> = declarations.iter().map(|declaration| declaration.manifest.clone()).collect();
+    manifests.sort();
+    manifests.dedup();
+    manifests
+}
+
+
+/// Finds the inconsistent nanoservice declarations in the graph.
+///
+/// # Arguments
+/// * `graph` - The nanoservice dependency graph.
+/// * `config` - The configuration with the active profile that selects the images.
+///
+/// # Returns
+/// All the conflicts found, ordered by nanoservice name and then by image (without the tag).
+pub fn find_conflicts(graph: &NanoserviceGraph, config: &NanoforgeConfig) -> Vec<Conflict> {
+    let image = |declaration: &Declaration| {
+        declaration.nanoservice.image(&config.profile)
+            .map(|image| ImageReference::parse(image).name())
+            .unwrap_or_default()
+    };
+    let entrypoint = |declaration: &Declaration| declaration.nanoservice.entrypoint.clone();
+    let kernels = |declaration: &Declaration| {
+        let mut kernels: Vec<&str> = declaration.nanoservice.all_kernels().iter().map(|kernel| kernel.name.as_str()).collect();
+        kernels.sort();
+        match kernels.is_empty() {
+            true => "no kernels".to_string(),
+            false => kernels.join(", ")
+        }
+    };
+
+    let mut by_name: BTreeMap<&String, Vec<Declaration>> = BTreeMap::new();
+    let mut by_image: BTreeMap<String, Vec<Declaration>> = BTreeMap::new();
+    for manifest in &graph.manifests {
+        for (name, nanoservice) in &manifest.nanoservices {
+            let declaration = Declaration { manifest: &manifest.path, name, nanoservice };
+            by_image.entry(image(&declaration)).or_default().push(
+                Declaration { manifest: &manifest.path, name, nanoservice }
+            );
+            by_name.entry(name).or_default().push(declaration);
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    for (name, declarations) in &by_name {
+        let fields: [(&str, DeclarationValue); 3] = [
+            ("images", &image),
+            ("entrypoints", &entrypoint),
+            ("kernels", &kernels)
+        ];
+        for (field, value) in fields {
+            if let Some(differences) = describe_differences(declarations, value) {
+                conflicts.push(Conflict {
+                    message: format!("nanoservice `{}` is declared with different {}: {}", name, field, differences),
+                    manifests: manifests_of(declarations),
+                });
+            }
+        }
+    }
+    for (image, declarations) in &by_image {
+        let name_and_entrypoint = |declaration: &Declaration| {
+            format!("{}` with entrypoint `{}", declaration.name, declaration.nanoservice.entrypoint)
+        };
+        let names = describe_differences(declarations, |declaration| declaration.name.clone());
+        let entrypoints = describe_differences(declarations, entrypoint);
+        if names.is_some() && entrypoints.is_some() {
+            conflicts.push(Conflict {
+                message: format!(
+                    "image `{}` is declared as different nanoservices with different entrypoints: {}",
+                    image,
+                    describe_differences(declarations, name_and_entrypoint).unwrap_or_default()
+                ),
+                manifests: manifests_of(declarations),
+            });
+        }
+    }
+    conflicts
+}
+
+
+/// Reports the inconsistent nanoservice declarations in the graph according to the `conflicts` policy.
+///
+/// # Arguments
+/// * `graph` - The nanoservice dependency graph.
+/// * `config` - The configuration with the active profile and the `conflicts` policy.
+///
+/// # Returns
+/// An error listing all the conflicts if there are any and the policy is `error`.
+pub fn check_conflicts(graph: &NanoserviceGraph, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let conflicts = find_conflicts(graph, config);
+    if conflicts.is_empty() {
+        return Ok(())
+    }
+    match config.conflicts {
+        ConflictPolicy::Warn => {
+            for conflict in conflicts {
+                eprintln!("warning: {}", conflict);
+            }
+            Ok(())
+        },
+        ConflictPolicy::Error => Err(NanoServiceError::new(
+            format!(
+                "Inconsistent nanoservice declarations:\n{}",
+                conflicts.iter().map(|conflict| conflict.to_string()).collect::<Vec<String>>().join("\n")
+            ),
+            NanoServiceErrorStatus::BadRequest
+        ))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::resolver::ManifestNode;
+
+    #[test]
+    fn test_find_conflicts() {
+        let graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode::fixture("one/Cargo.toml", &[("nan-one", "acme/one:1.0.0", "."), ("nan-two", "acme/two:1.0.0", ".")]),
+                ManifestNode::fixture("two/Cargo.toml", &[("nan-one", "acme/other:1.0.0", "."), ("two", "acme/two", "lib")]),
+                ManifestNode::fixture("three/Cargo.toml", &[("nan-two", "acme/two:1.1.0", ".")]),
+            ],
+            images: BTreeMap::new(),
+            ..NanoserviceGraph::default()
+        };
+
+        assert_eq!(
+            vec![
+                Conflict {
+                    message: "nanoservice `nan-one` is declared with different images: \
+                        `acme/one` (one/Cargo.toml), `acme/other` (two/Cargo.toml)".to_string(),
+                    manifests: vec![PathBuf::from("one/Cargo.toml"), PathBuf::from("two/Cargo.toml")],
+                },
+                Conflict {
+                    message: "image `acme/two` is declared as different nanoservices with different entrypoints: \
+                        `nan-two` with entrypoint `.` (one/Cargo.toml, three/Cargo.toml), `two` with entrypoint `lib` (two/Cargo.toml)".to_string(),
+                    manifests: vec![PathBuf::from("one/Cargo.toml"), PathBuf::from("three/Cargo.toml"), PathBuf::from("two/Cargo.toml")],
+                },
+            ],
+            find_conflicts(&graph, &NanoforgeConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_check_conflicts_error_policy() {
+        let graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode::fixture("one/Cargo.toml", &[("nan-one", "acme/one", ".")]),
+                ManifestNode::fixture("two/Cargo.toml", &[("nan-one", "acme/one", "lib")]),
+            ],
+            images: BTreeMap::new(),
+            ..NanoserviceGraph::default()
+        };
+        let config = NanoforgeConfig {
+            conflicts: ConflictPolicy::Error,
+            ..NanoforgeConfig::default()
+        };
+
+        assert_eq!(true, check_conflicts(&graph, &NanoforgeConfig::default()).is_ok());
+        assert_eq!(
+            "Inconsistent nanoservice declarations:\n\
+            nanoservice `nan-one` is declared with different entrypoints: `.` (one/Cargo.toml), `lib` (two/Cargo.toml)",
+            check_conflicts(&graph, &config).unwrap_err().message
+        );
+    }
+
+}
@@ -0,0 +1,384 @@
+//! Defines the dependency graph that the graph and the tree of the nanoservices are rendered from.
+//!
+//! # Notes
+//! There are three kinds of nodes: the crates that consume nanoservices, the images of the nanoservices, and the kernel
+//! crates inside the images. An edge goes from a dependency to the node depending on it:
+//! * an image or kernel points at the crates declaring it, labelled with the name, features, and profile
+//! * a crate inside an image points at the image
+//! * a kernel points at the image it is packaged in
+//!
+//! The graph is only read from the resolved nanoservice graph so building it never writes anything.
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use serde::Serialize;
+use nanoservices_utils::errors::NanoServiceError;
+use crate::docker_files::cache::{nanoservices_cache_dir, CURRENT_WORKING_DIR};
+use crate::docker_files::registry::ImageReference;
+use crate::project_config::config::NanoforgeConfig;
+use crate::toml_operations::nanoservices::resolver::{image_dir, NanoserviceGraph};
+use crate::toml_operations::nanoservices::unify::Unification;
+
+
+/// The kind of a node in the dependency graph.
+///
+/// # Variants
+/// * `Crate` - A crate with a `Cargo.toml` file, either in the project or inside an image.
+/// * `Image` - The image of a nanoservice, with its tag or digest.
+/// * `Kernel` - A kernel crate inside an image.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Crate,
+    Image,
+    Kernel,
+}
+
+
+/// A node of the dependency graph.
+///
+/// # Fields
+/// * `id` - The unique key of the node (the image with its tag, or the path to the `Cargo.toml` file of a crate).
+/// * `kind` - The kind of the node.
+/// * `label` - The package name and path of a crate, the image with its tag or digest, or the name of a kernel.
+/// * `local` - If the image is local and never pulled (images and kernels only).
+/// * `cached` - If the image is unpacked in the cache (always `true` for crates that were read).
+/// * `in_cycle` - If the node is part of a circular nanoservice dependency.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub kind: NodeKind,
+    pub label: String,
+    pub local: bool,
+    pub cached: bool,
+    pub in_cycle: bool,
+}
+
+
+/// An edge of the dependency graph from a dependency to the node depending on it.
+///
+/// # Fields
+/// * `from` - The index of the dependency in the nodes.
+/// * `to` - The index of the node depending on it in the nodes.
+/// * `label` - The dependency name with its features and the profile the image was selected for (optional).
+/// * `in_cycle` - If the edge is part of a circular nanoservice dependency.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub label: Option<String>,
+    pub in_cycle: bool,
+}
+
+
+/// The dependency graph of the nanoservices.
+///
+/// # Fields
+/// * `nodes` - The nodes in the order they were found.
+/// * `edges` - The edges in the order they were found.
+/// * `offline` - If the graph was built in the offline mode where only the cached images are used.
+/// * `unifications` - The images that were declared with different tags and unified to one of them.
+/// * `index` - The index of every node in the nodes keyed by the key of the node.
+/// * `edge_keys` - The dependency, the node depending on it, and the label of every edge.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct DependencyGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    pub offline: bool,
+    pub unifications: Vec<Unification>,
+    #[serde(skip)]
+    index: HashMap<String, usize>,
+    #[serde(skip)]
+    edge_keys: HashSet<(usize, usize, Option<String>)>,
+}
+
+
+/// Removes the `.` components of a path so the same `Cargo.toml` file always has the same key.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().filter(|component| *component != Component::CurDir).collect()
+}
+
+
+/// Gets the key of the node of an image, an image without a tag or digest is the `latest` tag of the image.
+///
+/// # Arguments
+/// * `image` - The image as it is declared.
+///
+/// # Returns
+/// The image with its tag.
+pub fn image_id(image: &str) -> String {
+    match image.contains('@') || ImageReference::parse(image).tag.is_some() {
+        true => image.to_string(),
+        false => format!("{}:latest", image)
+    }
+}
+
+
+/// Labels an edge with the dependency name, its features, and the profile.
+fn edge_label(name: &str, features: &Option<Vec<String>>, config: &NanoforgeConfig) -> String {
+    match features.as_ref().filter(|features| !features.is_empty()) {
+        Some(features) => format!("{} [{}] ({})", name, features.join(", "), config.profile),
+        None => format!("{} ({})", name, config.profile)
+    }
+}
+
+
+impl DependencyGraph {
+
+    /// Creates a dependency graph from its nodes and edges.
+    ///
+    /// # Arguments
+    /// * `nodes` - The nodes, every node needs a unique key.
+    /// * `edges` - The edges between the nodes, every edge needs a unique dependency, node, and label.
+    ///
+    /// # Returns
+    /// The dependency graph with the nodes indexed by their keys.
+    pub fn new(nodes: Vec<GraphNode>, edges: Vec<GraphEdge>) -> DependencyGraph {
+        let index = nodes.iter().enumerate().map(|(position, node)| (node.id.clone(), position)).collect();
+        let edge_keys = edges.iter().map(|edge| (edge.from, edge.to, edge.label.clone())).collect();
+        DependencyGraph { nodes, edges, index, edge_keys, ..DependencyGraph::default() }
+    }
+
+    /// Builds the dependency graph from the resolved nanoservice graph.
+    ///
+    /// # Notes
+    /// The nodes and edges of the first cycle found are marked as `in_cycle`.
+    ///
+    /// # Arguments
+    /// * `graph` - The resolved nanoservice graph.
+    /// * `config` - The configuration with the cache, the offline mode, and the active profile that selects the images.
+    ///
+    /// # Returns
+    /// The dependency graph.
+    pub fn build(graph: &NanoserviceGraph, config: &NanoforgeConfig) -> Result<DependencyGraph, NanoServiceError> {
+        // the crates inside the cache are labelled without the path to the cache of the profile
+        let cache_dir = nanoservices_cache_dir(config);
+        let cache_prefix = format!(
+            "{}/",
+            cache_dir.strip_prefix(&*CURRENT_WORKING_DIR).unwrap_or(&cache_dir).display()
+        );
+        let short_path = |path: &Path| path.display().to_string().replace(&cache_prefix, "nanoservice:");
+
+        // the crates at the entrypoint of a kernel are kernels rather than plain crates
+        let mut kernel_paths = HashSet::new();
+        for (_, nanoservice) in graph.all_nanoservices() {
+            for kernel in nanoservice.all_kernels() {
+                let image = nanoservice.kernel_image(kernel, &config.profile)?;
+                kernel_paths.insert(normalize(&image_dir(image, config).join(&kernel.entrypoint).join("Cargo.toml")));
+            }
+        }
+
+        let mut dependency_graph = DependencyGraph {
+            offline: config.offline,
+            unifications: graph.unifications.clone(),
+            ..DependencyGraph::new(Vec::new(), Vec::new())
+        };
+        let mut manifest_nodes = Vec::new();
+        for manifest in &graph.manifests {
+            let kind = match kernel_paths.contains(&normalize(&manifest.path)) {
+                true => NodeKind::Kernel,
+                false => NodeKind::Crate
+            };
+            let label = format!("{} ({})", manifest.cargo_toml.package.name, short_path(&manifest.path));
+            manifest_nodes.push(
+                dependency_graph.node(&normalize(&manifest.path).display().to_string(), kind, &label, false, true)
+            );
+        }
+        for (image, paths) in &graph.images {
+            let image_node = dependency_graph.image_node(graph, image, config);
+            for path in paths {
+                if let Some(crate_node) = dependency_graph.position(&normalize(path).display().to_string()) {
+                    dependency_graph.edge(crate_node, image_node, None);
+                }
+            }
+        }
+
+        for (manifest, manifest_node) in graph.manifests.iter().zip(manifest_nodes) {
+            for (name, nanoservice) in &manifest.nanoservices {
+                let image = nanoservice.image(&config.profile)?;
+                let image_node = dependency_graph.image_node(graph, image, config);
+                dependency_graph.edge(image_node, manifest_node, Some(edge_label(name, &nanoservice.features, config)));
+
+                for kernel in nanoservice.all_kernels() {
+                    let kernel_image = nanoservice.kernel_image(kernel, &config.profile)?;
+                    let kernel_image_node = dependency_graph.image_node(graph, kernel_image, config);
+                    let path = normalize(&image_dir(kernel_image, config).join(&kernel.entrypoint).join("Cargo.toml"));
+                    // a kernel inside an image that is not cached has no Cargo.toml file to label it with
+                    let kernel_node = match dependency_graph.position(&path.display().to_string()) {
+                        Some(kernel_node) => kernel_node,
+                        None => {
+                            let local = dependency_graph.nodes[kernel_image_node].local;
+                            let kernel_node = dependency_graph.node(
+                                &path.display().to_string(), NodeKind::Kernel, &kernel.name, local, false
+                            );
+                            dependency_graph.edge(kernel_node, kernel_image_node, None);
+                            kernel_node
+                        }
+                    };
+                    dependency_graph.edge(kernel_node, manifest_node, Some(edge_label(&kernel.name, &kernel.features, config)));
+                }
+            }
+        }
+
+        if let Some(cycle) = graph.find_cycle(config) {
+            let mut cycle_edges = HashSet::new();
+            for (index, step) in cycle.0.iter().enumerate() {
+                let next = &cycle.0[(index + 1) % cycle.0.len()];
+                cycle_edges.insert((image_id(&step.image), normalize(&step.manifest).display().to_string()));
+                cycle_edges.insert((normalize(&next.manifest).display().to_string(), image_id(&step.image)));
+            }
+            for edge in dependency_graph.edges.iter_mut() {
+                let key = (dependency_graph.nodes[edge.from].id.clone(), dependency_graph.nodes[edge.to].id.clone());
+                if cycle_edges.contains(&key) {
+                    edge.in_cycle = true;
+                    dependency_graph.nodes[edge.from].in_cycle = true;
+                    dependency_graph.nodes[edge.to].in_cycle = true;
+                }
+            }
+        }
+        Ok(dependency_graph)
+    }
+
+    /// Gets the index of the node with a key.
+    ///
+    /// # Arguments
+    /// * `id` - The key of the node.
+    ///
+    /// # Returns
+    /// The index of the node if it is in the graph.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.index.get(id).copied()
+    }
+
+    /// Gets the index of the node with a key, adding the node if it is not in the graph yet.
+    fn node(&mut self, id: &str, kind: NodeKind, label: &str, local: bool, cached: bool) -> usize {
+        if let Some(index) = self.position(id) {
+            return index
+        }
+        self.nodes.push(GraphNode {
+            id: id.to_string(),
+            kind,
+            label: label.to_string(),
+            local,
+            cached,
+            in_cycle: false,
+        });
+        self.index.insert(id.to_string(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Gets the index of the node of an image, adding the node if it is not in the graph yet.
+    fn image_node(&mut self, graph: &NanoserviceGraph, image: &str, config: &NanoforgeConfig) -> usize {
+        let id = image_id(image);
+        if let Some(index) = self.position(&id) {
+            return index
+        }
+        let local = graph.all_nanoservices().iter().any(|(_, nanoservice)| {
+            nanoservice.local.unwrap_or(false)
+                && nanoservice.images(&config.profile).unwrap_or_default().iter().any(|declared| *declared == image)
+        });
+        let cached = image_dir(image, config).exists();
+        self.node(&id, NodeKind::Image, &id, local, cached)
+    }
+
+    /// Adds an edge from a dependency to the node depending on it if the edge is not in the graph yet.
+    fn edge(&mut self, from: usize, to: usize, label: Option<String>) {
+        if self.edge_keys.insert((from, to, label.clone())) {
+            self.edges.push(GraphEdge { from, to, label, in_cycle: false });
+        }
+    }
+
+    /// Gets the dependencies of a node.
+    ///
+    /// # Arguments
+    /// * `node` - The index of the node.
+    ///
+    /// # Returns
+    /// The edges to the node in the order they were found.
+    pub fn dependencies(&self, node: usize) -> Vec<&GraphEdge> {
+        self.edges.iter().filter(|edge| edge.to == node).collect()
+    }
+
+    /// Gets the dependents of a node.
+    ///
+    /// # Arguments
+    /// * `node` - The index of the node.
+    ///
+    /// # Returns
+    /// The edges from the node in the order they were found.
+    pub fn dependents(&self, node: usize) -> Vec<&GraphEdge> {
+        self.edges.iter().filter(|edge| edge.from == node).collect()
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::toml_operations::file_ops::read::read_toml;
+    use crate::toml_operations::nanoservices::kernel::Nanoservice;
+    use crate::toml_operations::nanoservices::resolver::ManifestNode;
+
+    #[test]
+    fn test_build() {
+        let config = NanoforgeConfig::default();
+        let nanoservice: Nanoservice = toml::from_str(
+            "dev_image = \"acme/one:1.0.0\"\nentrypoint = \".\"\nfeatures = [\"json\"]\nlocal = true\n\
+            kernel = { name = \"one-kernel\", entrypoint = \"kernel\" }"
+        ).unwrap();
+        let image_manifest = image_dir("acme/one:1.0.0", &config).join("Cargo.toml");
+        let cargo_toml = read_toml(Path::new("./tests/assets/Cargo.toml")).unwrap().into_raw().unwrap();
+        let graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode {
+                    path: PathBuf::from("Cargo.toml"),
+                    cargo_toml: cargo_toml.clone(),
+                    nanoservices: vec![("one".to_string(), nanoservice)],
+                    has_managed: false,
+                },
+                ManifestNode {
+                    path: image_manifest.clone(),
+                    cargo_toml: cargo_toml.clone(),
+                    nanoservices: Vec::new(),
+                    has_managed: false,
+                },
+            ],
+            images: BTreeMap::from([("acme/one:1.0.0".to_string(), vec![image_manifest])]),
+            ..NanoserviceGraph::default()
+        };
+
+        let dependency_graph = DependencyGraph::build(&graph, &config).unwrap();
+
+        let name = &cargo_toml.package.name;
+        assert_eq!(
+            vec![
+                (NodeKind::Crate, format!("{} (Cargo.toml)", name)),
+                (NodeKind::Crate, format!("{} (nanoservice:acme_one_1.0.0/Cargo.toml)", name)),
+                (NodeKind::Image, "acme/one:1.0.0".to_string()),
+                (NodeKind::Kernel, "one-kernel".to_string()),
+            ],
+            dependency_graph.nodes.iter().map(|node| (node.kind, node.label.clone())).collect::<Vec<(NodeKind, String)>>()
+        );
+        assert_eq!(Some(2), dependency_graph.position("acme/one:1.0.0"));
+        assert_eq!(true, dependency_graph.nodes[2].local);
+        assert_eq!(false, dependency_graph.nodes[2].cached);
+        assert_eq!(
+            vec![(2, Some("one [json] (dev)".to_string())), (3, Some("one-kernel (dev)".to_string()))],
+            dependency_graph.dependencies(0).iter()
+                .map(|edge| (edge.from, edge.label.clone()))
+                .collect::<Vec<(usize, Option<String>)>>()
+        );
+        assert_eq!(vec![1, 3], dependency_graph.dependencies(2).iter().map(|edge| edge.from).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_image_id() {
+        assert_eq!("acme/one:latest", image_id("acme/one"));
+        assert_eq!("localhost:5000/acme/one:latest", image_id("localhost:5000/acme/one"));
+        assert_eq!("acme/one:1.0.0", image_id("acme/one:1.0.0"));
+        assert_eq!("acme/one@sha256:abc", image_id("acme/one@sha256:abc"));
+    }
+
+}
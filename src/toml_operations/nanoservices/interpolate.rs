@@ -0,0 +1,183 @@
+//! Defines the expansion of variables in the image references of nanoservices.
+//!
+//! # Notes
+//! Image references can contain `${NAME}` and `${NAME:-default}`. The variables are looked up in the environment
+//! first and then in the `[vars]` table of the `nanoforge.toml` file. A variable that is not defined anywhere
+//! and has no default is an error.
+use std::collections::HashMap;
+use std::path::Path;
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+use crate::toml_operations::nanoservices::kernel::Nanoservice;
+
+
+/// The reasons that the variables in a string cannot be expanded.
+///
+/// # Variants
+/// * `Undefined` - A variable that is not defined anywhere and has no default, with the name of the variable.
+/// * `Unclosed` - A `${` without a closing `}`, with the unclosed placeholder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpolationError {
+    Undefined(String),
+    Unclosed(String),
+}
+
+
+/// Expands all the `${NAME}` and `${NAME:-default}` variables in a string.
+///
+/// # Arguments
+/// * `value` - The string to expand the variables in.
+/// * `vars` - The variables defined in the project config (the environment takes precedence).
+/// * `env` - Looks up a variable in the environment.
+///
+/// # Returns
+/// The expanded string, or the first undefined variable or unclosed placeholder as the error.
+pub fn interpolate(
+        value: &str,
+        vars: &HashMap<String, String>,
+        env: impl Fn(&str) -> Option<String>
+    ) -> Result<String, InterpolationError> {
+    let mut outcome = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        outcome.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(InterpolationError::Unclosed(rest[start..].to_string()))
+        };
+        let reference = &rest[start + 2..end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None)
+        };
+        let resolved = env(name)
+            .or_else(|| vars.get(name).cloned())
+            .or_else(|| default.map(|default| default.to_string()));
+        match resolved {
+            Some(resolved) => outcome.push_str(&resolved),
+            None => return Err(InterpolationError::Undefined(name.to_string()))
+        }
+        rest = &rest[end + 1..];
+    }
+    outcome.push_str(rest);
+    Ok(outcome)
+}
+
+
+/// Expands the variables in the image references of a nanoservice.
+///
+/// # Arguments
+/// * `name` - The name of the nanoservice (used in the error).
+/// * `nanoservice` - The nanoservice to expand the image references of.
+/// * `vars` - The variables defined in the project config.
+/// * `cargo_path` - The path to the Cargo.toml file declaring the nanoservice (used in the error).
+///
+/// # Returns
+/// A copy of the nanoservice with the variables in the image references expanded.
+pub fn interpolate_nanoservice(
+        name: &str,
+        nanoservice: &Nanoservice,
+        vars: &HashMap<String, String>,
+        cargo_path: &Path
+    ) -> Result<Nanoservice, NanoServiceError> {
+    let expand = |field: &str, value: &str| {
+        interpolate(value, vars, |key| std::env::var(key).ok()).map_err(|error| {
+            let reason = match error {
+                InterpolationError::Undefined(variable) => format!("Undefined variable `{}`", variable),
+                InterpolationError::Unclosed(placeholder) => format!("Unclosed placeholder `{}`", placeholder)
+            };
+            NanoServiceError::new(
+                format!("{} in `{}` of nanoservice `{}` in {}", reason, field, name, cargo_path.display()),
+                NanoServiceErrorStatus::BadRequest
+            )
+        })
+    };
+    let mut nanoservice = nanoservice.clone();
+    if let Some(image) = &nanoservice.dev_image {
+        nanoservice.dev_image = Some(expand("dev_image", image)?);
+    }
+    if let Some(image) = &nanoservice.prod_image {
+        nanoservice.prod_image = Some(expand("prod_image", image)?);
+    }
+    for (profile, image) in nanoservice.images.iter_mut().flatten() {
+        *image = expand(&format!("images.{}", profile), image)?;
+    }
+    for kernel in nanoservice.kernel.iter_mut().chain(nanoservice.kernels.iter_mut().flatten()) {
+        if let Some(image) = &kernel.image {
+            kernel.image = Some(expand("kernel.image", image)?);
+        }
+    }
+    Ok(nanoservice)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        let mut vars = HashMap::new();
+        vars.insert("NANOFORGE_TEST_REGISTRY".to_string(), "registry.example.com".to_string());
+
+        assert_eq!(
+            Ok("registry.example.com/nan-one:latest".to_string()),
+            interpolate("${NANOFORGE_TEST_REGISTRY}/nan-one:${NANOFORGE_TEST_TAG:-latest}", &vars, |_| None)
+        );
+        assert_eq!(
+            Ok("maxwellflitton/nan-one".to_string()),
+            interpolate("maxwellflitton/nan-one", &vars, |_| None)
+        );
+        assert_eq!(
+            Err(InterpolationError::Undefined("NANOFORGE_TEST_UNDEFINED".to_string())),
+            interpolate("${NANOFORGE_TEST_UNDEFINED}/nan-one", &vars, |_| None)
+        );
+    }
+
+    #[test]
+    fn test_interpolate_unclosed() {
+        let mut vars = HashMap::new();
+        vars.insert("NANOFORGE_TEST_REGISTRY".to_string(), "registry.example.com".to_string());
+
+        assert_eq!(
+            Err(InterpolationError::Unclosed("${TAG".to_string())),
+            interpolate("registry/nan-one:${TAG", &vars, |_| None)
+        );
+        assert_eq!(
+            Err(InterpolationError::Unclosed("${TAG".to_string())),
+            interpolate("${NANOFORGE_TEST_REGISTRY}/nan-one:${TAG", &vars, |_| None)
+        );
+    }
+
+    #[test]
+    fn test_interpolate_environment_takes_precedence() {
+        let mut vars = HashMap::new();
+        vars.insert("TAG".to_string(), "v1".to_string());
+        let env = |key: &str| (key == "TAG").then(|| "v2".to_string());
+
+        assert_eq!(Ok("nan-one:v2".to_string()), interpolate("nan-one:${TAG}", &vars, env));
+        assert_eq!(Ok("nan-one:v1".to_string()), interpolate("nan-one:${TAG}", &vars, |_| None));
+    }
+
+    #[test]
+    fn test_interpolate_nanoservice_undefined() {
+        let mut nanoservice = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        nanoservice.dev_image = Some("${NANOFORGE_TEST_MISSING}/nan-one".to_string());
+        let error = interpolate_nanoservice(
+            "nan-one",
+            &nanoservice,
+            &HashMap::new(),
+            Path::new("services/Cargo.toml")
+        ).unwrap_err();
+
+        assert_eq!(
+            "Undefined variable `NANOFORGE_TEST_MISSING` in `dev_image` of nanoservice `nan-one` in services/Cargo.toml",
+            error.message
+        );
+    }
+
+}
@@ -9,42 +9,57 @@ use nanoservices_utils::{
     }
 };
 use crate::toml_operations::file_ops::calculate_paths::calculate_relative_path;
+use crate::project_config::profile::{Profile, DEV_PROFILE, PROD_PROFILE};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
 
 
-/// Represents the structure of a nanoservice in a Cargo.toml file.
+/// Extra keys that are passed through untouched into a generated dependency entry, for example
+/// `registry` or `public`.
 ///
-/// # Fields
-/// * `dev_image` - The development Docker image of the nanoservice (to pull in dev environments).
-/// * `prod_image` - The production Docker image of the nanoservice (to pull in prod environments).
-/// * `entrypoint` - The entrypoint of the nanoservice (where the terminal has to point inside for the build).
-/// * `features` - The enabled features of the nanoservice (optional).
-/// * `local` - A flag to indicate if the nanoservice image is local and should not be pulled (optional).
-/// * `package` - The package name of the nanoservice (optional). If set then the name of the nanoservice will be
-///               the alias of the package name.
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
-pub struct Nanoservice {
-    pub dev_image: String,
-    pub prod_image: String,
-    pub entrypoint: String,
-    pub features: Option<Vec<String>>,
-    pub local: Option<bool>,
-    pub package: Option<String>,
-    pub kernel: Option<NanoserviceKernel>,
+/// # Notes
+/// `toml::Table` does not implement `Eq` or `Hash` so this wrapper hashes the serialized table, this
+/// keeps the nanoservices hashable for the `HashSet` of all nanoservices.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct ExtraKeys(pub Table);
+
+impl Eq for ExtraKeys {}
+
+impl Hash for ExtraKeys {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_string().hash(state);
+    }
 }
 
 
-// TODO => look into abstracting out the add_features and add_package functions into a trait
-impl Nanoservice {
+/// The shared behaviour of the structs that generate a dependency entry in a Cargo.toml file.
+pub trait DependencyEntry {
+
+    /// The enabled features of the dependency.
+    fn features(&self) -> &Option<Vec<String>>;
+
+    /// The package name that the dependency is an alias of.
+    fn package(&self) -> &Option<String>;
 
-    /// Adds features to the nanoservice table if the features exist.
+    /// The `default-features` flag of the dependency.
+    fn default_features(&self) -> Option<bool>;
+
+    /// Checks if the dependency should be declared as optional.
+    fn is_optional(&self) -> bool;
+
+    /// The extra keys that are passed through into the dependency entry.
+    fn extra(&self) -> &Option<ExtraKeys>;
+
+    /// Adds features to the dependency table if the features exist.
     /// 
     /// # Arguments
-    /// * `nanoservice_table` - The table to add the features to.
-    pub fn add_features(&self, nanoservice_table: &mut Map<String, Value>) {
-        match &self.features {
+    /// * `dependency_table` - The table to add the features to.
+    fn add_features(&self, dependency_table: &mut Map<String, Value>) {
+        match self.features() {
             Some(features) => {
-                nanoservice_table.insert(
+                dependency_table.insert(
                     "features".to_string(),
                     Value::Array(
                         features.iter().map(|f|
@@ -57,14 +72,14 @@ impl Nanoservice {
         }
     }
 
-    /// Adds the package to the nanoservice table if the package exists.
+    /// Adds the package to the dependency table if the package exists.
     /// 
     /// # Arguments
-    /// * `nanoservice_table` - The table to add the package to.
-    pub fn add_package(&self, nanoservice_table: &mut Map<String, Value>) {
-        match &self.package {
+    /// * `dependency_table` - The table to add the package to.
+    fn add_package(&self, dependency_table: &mut Map<String, Value>) {
+        match self.package() {
             Some(package) => {
-                nanoservice_table.insert(
+                dependency_table.insert(
                     "package".to_string(),
                     Value::String(package.to_string())
                 );
@@ -73,48 +88,344 @@ impl Nanoservice {
         }
     }
 
-    pub fn construct_kernel(
+    /// Adds the `default-features` flag to the dependency table if the flag is set.
+    ///
+    /// # Arguments
+    /// * `dependency_table` - The table to add the flag to.
+    fn add_default_features(&self, dependency_table: &mut Map<String, Value>) {
+        if let Some(default_features) = self.default_features() {
+            dependency_table.insert(
+                "default-features".to_string(),
+                Value::Boolean(default_features)
+            );
+        }
+    }
+
+    /// Marks the dependency in the dependency table as optional if the dependency is optional.
+    ///
+    /// # Arguments
+    /// * `dependency_table` - The table to add the optional flag to.
+    fn add_optional(&self, dependency_table: &mut Map<String, Value>) {
+        if self.is_optional() {
+            dependency_table.insert(
+                "optional".to_string(),
+                Value::Boolean(true)
+            );
+        }
+    }
+
+    /// Adds the extra keys to the dependency table, keys that are already in the table are not overwritten.
+    ///
+    /// # Arguments
+    /// * `dependency_table` - The table to add the extra keys to.
+    fn add_extra(&self, dependency_table: &mut Map<String, Value>) {
+        if let Some(extra) = self.extra() {
+            for (key, value) in extra.0.iter() {
+                if !dependency_table.contains_key(key) {
+                    dependency_table.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// Adds all the keys apart from the `path` to the dependency table.
+    ///
+    /// # Arguments
+    /// * `dependency_table` - The table to add the keys to.
+    fn add_dependency_keys(&self, dependency_table: &mut Map<String, Value>) {
+        self.add_features(dependency_table);
+        self.add_package(dependency_table);
+        self.add_default_features(dependency_table);
+        self.add_optional(dependency_table);
+        // extra keys go last so they can never overwrite the keys managed by NanoForge
+        self.add_extra(dependency_table);
+    }
+
+}
+
+
+/// Represents the structure of a nanoservice in a Cargo.toml file.
+///
+/// # Fields
+/// * `dev_image` - The shorthand for the image of the `dev` profile (optional).
+/// * `prod_image` - The shorthand for the image of the `prod` profile (optional).
+/// * `images` - The Docker image of the nanoservice for each profile (optional), takes precedence over the shorthands.
+/// * `entrypoint` - The entrypoint of the nanoservice (where the terminal has to point inside for the build).
+/// * `features` - The enabled features of the nanoservice (optional).
+/// * `local` - A flag to indicate if the nanoservice image is local and should not be pulled (optional).
+/// * `package` - The package name of the nanoservice (optional). If set then the name of the nanoservice will be
+///               the alias of the package name.
+/// * `kernel` - The kernel of the nanoservice (optional).
+/// * `kernels` - Additional kernels of the nanoservice such as separate "types" and "client" crates (optional).
+/// * `optional` - A flag to indicate if the nanoservice is an optional dependency of the crate (optional).
+/// * `feature` - The Cargo feature that enables the nanoservice (optional). Implies `optional` if set.
+/// * `default_features` - The `default-features` flag of the generated dependency, also read from `default-features` (optional).
+/// * `extra` - Extra keys passed through into the generated dependency (optional).
+/// * `version` - A semver requirement that the tags of the images are resolved against (optional).
+/// * `profiles` - The settings that are overridden for each profile (optional).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct Nanoservice {
+    pub dev_image: Option<String>,
+    pub prod_image: Option<String>,
+    pub images: Option<BTreeMap<String, String>>,
+    pub entrypoint: String,
+    pub features: Option<Vec<String>>,
+    pub local: Option<bool>,
+    pub package: Option<String>,
+    pub kernel: Option<NanoserviceKernel>,
+    pub kernels: Option<Vec<NanoserviceKernel>>,
+    pub optional: Option<bool>,
+    pub feature: Option<String>,
+    #[serde(alias = "default-features")]
+    pub default_features: Option<bool>,
+    pub extra: Option<ExtraKeys>,
+    pub version: Option<String>,
+    pub profiles: Option<BTreeMap<String, ProfileOverrides>>,
+}
+
+
+/// The settings of a nanoservice that can be overridden for a profile.
+///
+/// # Fields
+/// * `entrypoint` - Overrides the entrypoint of the nanoservice (optional).
+/// * `features` - Overrides the enabled features of the nanoservice (optional).
+/// * `kernel` - Overrides the kernel of the nanoservice (optional).
+/// * `kernels` - Overrides the additional kernels of the nanoservice (optional).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ProfileOverrides {
+    pub entrypoint: Option<String>,
+    pub features: Option<Vec<String>>,
+    pub kernel: Option<NanoserviceKernel>,
+    pub kernels: Option<Vec<NanoserviceKernel>>,
+}
+
+
+impl DependencyEntry for Nanoservice {
+
+    fn features(&self) -> &Option<Vec<String>> {
+        &self.features
+    }
+
+    fn package(&self) -> &Option<String> {
+        &self.package
+    }
+
+    fn default_features(&self) -> Option<bool> {
+        self.default_features
+    }
+
+    /// `true` if `optional` is set to `true` or a `feature` is declared for the nanoservice.
+    fn is_optional(&self) -> bool {
+        self.optional.unwrap_or(false) || self.feature.is_some()
+    }
+
+    fn extra(&self) -> &Option<ExtraKeys> {
+        &self.extra
+    }
+
+}
+
+
+impl Nanoservice {
+
+    /// Gets all the kernels of the nanoservice declared under `kernel` and `kernels`.
+    ///
+    /// # Returns
+    /// The kernels with the `kernel` first followed by the `kernels` in the order they are declared.
+    pub fn all_kernels(&self) -> Vec<&NanoserviceKernel> {
+        self.kernel.iter().chain(self.kernels.iter().flatten()).collect()
+    }
+
+    /// Gets the image of the nanoservice for a profile.
+    ///
+    /// # Arguments
+    /// * `profile` - The active profile.
+    ///
+    /// # Returns
+    /// The image in `images` for the profile, falling back to the `dev_image` and `prod_image` shorthands.
+    pub fn image(&self, profile: &Profile) -> Result<&String, NanoServiceError> {
+        let shorthand = match profile.name() {
+            DEV_PROFILE => self.dev_image.as_ref(),
+            PROD_PROFILE => self.prod_image.as_ref(),
+            _ => None
+        };
+        match self.images.as_ref().and_then(|images| images.get(profile.name())).or(shorthand) {
+            Some(image) => Ok(image),
+            None => Err(NanoServiceError::new(
+                format!("No image is declared for the `{}` profile", profile),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        }
+    }
+
+    /// Sets the image of the nanoservice for a profile.
+    ///
+    /// # Arguments
+    /// * `profile` - The profile to set the image of.
+    /// * `image` - The image to set.
+    pub fn set_image(&mut self, profile: &Profile, image: String) {
+        self.images.get_or_insert_with(BTreeMap::new).insert(profile.name().to_string(), image);
+    }
+
+    /// Gets the names of all the profiles that the nanoservice declares an image for.
+    ///
+    /// # Returns
+    /// The sorted names of the profiles.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.images.iter().flatten().map(|(name, _)| name.clone()).collect();
+        if self.dev_image.is_some() {
+            names.push(DEV_PROFILE.to_string());
+        }
+        if self.prod_image.is_some() {
+            names.push(PROD_PROFILE.to_string());
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Applies the overrides of a profile to the nanoservice.
+    ///
+    /// # Arguments
+    /// * `profile` - The active profile.
+    ///
+    /// # Returns
+    /// A copy of the nanoservice with the settings of the profile.
+    pub fn for_profile(&self, profile: &Profile) -> Nanoservice {
+        let mut nanoservice = self.clone();
+        if let Some(overrides) = self.profiles.as_ref().and_then(|profiles| profiles.get(profile.name())) {
+            if let Some(entrypoint) = &overrides.entrypoint {
+                nanoservice.entrypoint = entrypoint.clone();
+            }
+            if overrides.features.is_some() {
+                nanoservice.features = overrides.features.clone();
+            }
+            if overrides.kernel.is_some() {
+                nanoservice.kernel = overrides.kernel.clone();
+            }
+            if overrides.kernels.is_some() {
+                nanoservice.kernels = overrides.kernels.clone();
+            }
+        }
+        nanoservice
+    }
+
+    /// Gets the image that a kernel of the nanoservice is packaged in.
+    ///
+    /// # Arguments
+    /// * `kernel` - The kernel to get the image of.
+    /// * `profile` - The active profile.
+    ///
+    /// # Returns
+    /// The `image` of the kernel if set, otherwise the image of the nanoservice for the profile.
+    pub fn kernel_image<'a>(&'a self, kernel: &'a NanoserviceKernel, profile: &Profile) -> Result<&'a String, NanoServiceError> {
+        match &kernel.image {
+            Some(image) => Ok(image),
+            None => self.image(profile)
+        }
+    }
+
+    /// Gets all the images that have to be pulled for the nanoservice and its kernels.
+    ///
+    /// # Arguments
+    /// * `profile` - The active profile.
+    ///
+    /// # Returns
+    /// The image for the profile followed by the images of the kernels that are packaged separately, without duplicates.
+    pub fn images(&self, profile: &Profile) -> Result<Vec<&String>, NanoServiceError> {
+        let mut images = vec![self.image(profile)?];
+        for kernel in self.all_kernels() {
+            let image = self.kernel_image(kernel, profile)?;
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+        Ok(images)
+    }
+
+    /// Constructs the dependency tables for all the kernels of the nanoservice.
+    ///
+    /// # Arguments
+    /// * `cargo_toml_path` - The path to the Cargo.toml file that the kernels are being added to.
+    /// * `nanoservices_path` - The path to the directory where the nanoservices are cached.
+    /// * `profile` - The active profile.
+    ///
+    /// # Returns
+    /// The name of each kernel with its dependency table.
+    pub fn construct_kernels(
             &self,
             cargo_toml_path: &PathBuf,
-            nanoservices_path: &PathBuf 
-        ) -> Result<Option<Map<String, Value>>, NanoServiceError> {
-        match &self.kernel {
-            Some(kernel) => {
-                // TODO => look into putting the path code below as a function into a utils file for the nanoservices
-                let mut kernel_table = Table::new();
-                let relative_path = safe_eject!(
-                    calculate_relative_path(
-                        &cargo_toml_path,
-                        &self.dev_image,
-                        &kernel.entrypoint,
-                        &nanoservices_path
-                    ),
-                    NanoServiceErrorStatus::Unknown,
-                    "Failed to calculate the relative path when configuring the Cargo.toml file"
-                )?;
-                let relative_path_str = match relative_path.to_str() {
-                    Some(v) => v,
-                    None => {
-                        return Err(
-                            NanoServiceError::new(
-                                "Failed to convert the relative path to a string when configuring the Cargo.toml file".to_string(),
-                                NanoServiceErrorStatus::Unknown
-                            )
+            nanoservices_path: &PathBuf,
+            profile: &Profile
+        ) -> Result<Vec<(String, Table)>, NanoServiceError> {
+        let mut kernel_tables = Vec::new();
+        for kernel in self.all_kernels() {
+            // TODO => look into putting the path code below as a function into a utils file for the nanoservices
+            let mut kernel_table = Table::new();
+            let relative_path = safe_eject!(
+                calculate_relative_path(
+                    cargo_toml_path,
+                    self.kernel_image(kernel, profile)?,
+                    &kernel.entrypoint,
+                    nanoservices_path
+                ),
+                NanoServiceErrorStatus::Unknown,
+                "Failed to calculate the relative path when configuring the Cargo.toml file"
+            )?;
+            let relative_path_str = match relative_path.to_str() {
+                Some(v) => v,
+                None => {
+                    return Err(
+                        NanoServiceError::new(
+                            "Failed to convert the relative path to a string when configuring the Cargo.toml file".to_string(),
+                            NanoServiceErrorStatus::Unknown
                         )
-                    }
-                }.to_string();
-                kernel_table.insert(
-                    "path".to_string(),
-                    Value::String(relative_path_str)
-                );
-                // add optional features
-                kernel.add_features(&mut kernel_table);
-                kernel.add_package(&mut kernel_table);
-                return Ok(Some(kernel_table))
-            },
-            None => ()
+                    )
+                }
+            }.to_string();
+            kernel_table.insert(
+                "path".to_string(),
+                Value::String(relative_path_str)
+            );
+            // add optional keys
+            kernel.add_dependency_keys(&mut kernel_table);
+            kernel_tables.push((kernel.name.clone(), kernel_table));
+        }
+        Ok(kernel_tables)
+    }
+
+}
+
+
+#[cfg(test)]
+impl Nanoservice {
+
+    /// Builds a nanoservice for the tests with the same image for the `dev` and `prod` profiles.
+    ///
+    /// # Arguments
+    /// * `image` - The `dev_image` and `prod_image` of the nanoservice.
+    /// * `entrypoint` - The entrypoint of the nanoservice.
+    ///
+    /// # Returns
+    /// The nanoservice with every optional setting unset.
+    pub fn fixture(image: &str, entrypoint: &str) -> Nanoservice {
+        Nanoservice {
+            dev_image: Some(image.to_string()),
+            prod_image: Some(image.to_string()),
+            images: None,
+            entrypoint: entrypoint.to_string(),
+            features: None,
+            local: None,
+            package: None,
+            kernel: None,
+            kernels: None,
+            optional: None,
+            feature: None,
+            default_features: None,
+            extra: None,
+            version: None,
+            profiles: None,
         }
-        return Ok(None)
     }
 
 }
@@ -128,51 +439,71 @@ impl Nanoservice {
 /// * `package` - The package name of the nanoservice kernel (optional). If set then the name of the nanoservice kernel will be
 ///               the alias of the package name.
 /// * `name` - The name of the nanoservice kernel, if the package is not set then this will be the name of the nanoservice kernel.
+/// * `optional` - A flag to indicate if the kernel is an optional dependency of the crate (optional).
+/// * `default_features` - The `default-features` flag of the generated dependency, also read from `default-features` (optional).
+/// * `extra` - Extra keys passed through into the generated dependency (optional).
+/// * `image` - The Docker image the kernel is packaged in if it is not packaged with the nanoservice (optional).
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 pub struct NanoserviceKernel {
     pub entrypoint: String,
     pub features: Option<Vec<String>>,
     pub package: Option<String>,
     pub name: String,
+    pub optional: Option<bool>,
+    #[serde(alias = "default-features")]
+    pub default_features: Option<bool>,
+    pub extra: Option<ExtraKeys>,
+    pub image: Option<String>,
 }
 
 
+#[cfg(test)]
 impl NanoserviceKernel {
 
-    /// Adds features to the nanoservice table if the features exist.
-    /// 
+    /// Builds a kernel for the tests that is packaged with its nanoservice.
+    ///
     /// # Arguments
-    /// * `nanoservice_table` - The table to add the features to.
-    pub fn add_features(&self, kernel_table: &mut Map<String, Value>) {
-        match &self.features {
-            Some(features) => {
-                kernel_table.insert(
-                    "features".to_string(),
-                    Value::Array(
-                        features.iter().map(|f|
-                                Value::String(f.to_string())
-                        ).collect()
-                    )
-                );
-            },
-            None => ()
+    /// * `name` - The name of the kernel.
+    /// * `entrypoint` - The entrypoint of the kernel.
+    ///
+    /// # Returns
+    /// The kernel with every optional setting unset.
+    pub fn fixture(name: &str, entrypoint: &str) -> NanoserviceKernel {
+        NanoserviceKernel {
+            entrypoint: entrypoint.to_string(),
+            features: None,
+            package: None,
+            name: name.to_string(),
+            optional: None,
+            default_features: None,
+            extra: None,
+            image: None,
         }
     }
 
-    /// Adds the package to the nanoservice table if the package exists.
-    /// 
-    /// # Arguments
-    /// * `nanoservice_table` - The table to add the package to.
-    pub fn add_package(&self, kernel_table: &mut Map<String, Value>) {
-        match &self.package {
-            Some(package) => {
-                kernel_table.insert(
-                    "package".to_string(),
-                    Value::String(package.to_string())
-                );
-            },
-            None => ()
-        }
+}
+
+
+impl DependencyEntry for NanoserviceKernel {
+
+    fn features(&self) -> &Option<Vec<String>> {
+        &self.features
     }
 
-}
\ No newline at end of file
+    fn package(&self) -> &Option<String> {
+        &self.package
+    }
+
+    fn default_features(&self) -> Option<bool> {
+        self.default_features
+    }
+
+    fn is_optional(&self) -> bool {
+        self.optional.unwrap_or(false)
+    }
+
+    fn extra(&self) -> &Option<ExtraKeys> {
+        &self.extra
+    }
+
+}
@@ -1,5 +1,14 @@
 pub mod kernel;
-pub mod get_all;
 pub mod wipe;
+pub mod ownership;
+pub mod shim;
+pub mod validate;
+pub mod interpolate;
+pub mod version;
+pub mod profile;
 pub mod configure_cargo_toml;
+pub mod resolver;
+pub mod conflicts;
+pub mod unify;
+pub mod dependency_graph;
 pub mod processes;
@@ -0,0 +1,208 @@
+//! Defines the ownership record of the dependencies that NanoForge injects into a `Cargo.toml` file.
+//!
+//! # Notes
+//! The record lives in the `[package.metadata.nanoforge]` table under the `managed` key. Only the dependencies
+//! listed in the record are touched when wiping the nanoservices from a `Cargo.toml` file. The features that
+//! NanoForge created to gate the dependencies are listed under the `features` key so they can be removed once they
+//! are empty, while empty features written by the user are kept. The `Cargo.toml` files
+//! that were prepped before the record existed fall back to the path dependencies pointing inside the cache.
+use std::path::{Component, Path, PathBuf};
+use toml::{Table, Value};
+use crate::toml_operations::kernel::RawCargoToml;
+
+
+/// The key of the NanoForge table in the package metadata.
+pub const NANOFORGE_METADATA_KEY: &str = "nanoforge";
+
+/// The key of the managed dependencies in the NanoForge metadata table.
+pub const MANAGED_KEY: &str = "managed";
+
+/// The key of the features created by NanoForge in the NanoForge metadata table.
+pub const FEATURES_KEY: &str = "features";
+
+
+/// Gets the names of the dependencies that NanoForge manages in the Cargo.toml file.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file to read the ownership record from.
+///
+/// # Returns
+/// The names of the managed dependencies (empty if there is no record).
+pub fn get_managed_dependencies(cargo_toml: &RawCargoToml) -> Vec<String> {
+    get_record(cargo_toml, MANAGED_KEY)
+}
+
+
+/// Gets the names of the features that NanoForge created in the Cargo.toml file.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file to read the ownership record from.
+///
+/// # Returns
+/// The names of the created features (empty if there is no record).
+pub fn get_managed_features(cargo_toml: &RawCargoToml) -> Vec<String> {
+    get_record(cargo_toml, FEATURES_KEY)
+}
+
+
+/// Gets the names listed under a key of the ownership record.
+fn get_record(cargo_toml: &RawCargoToml, key: &str) -> Vec<String> {
+    let managed = cargo_toml.package.metadata.as_ref()
+        .and_then(|metadata| metadata.get(NANOFORGE_METADATA_KEY))
+        .and_then(|nanoforge| nanoforge.get(key))
+        .and_then(|managed| managed.as_array());
+    match managed {
+        Some(managed) => managed.iter()
+            .filter_map(|name| name.as_str())
+            .map(|name| name.to_string())
+            .collect(),
+        None => Vec::new()
+    }
+}
+
+
+/// Removes the `.` and `..` components of a path without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => { normalized.pop(); },
+            component => normalized.push(component)
+        }
+    }
+    normalized
+}
+
+
+/// Checks if the path of a path dependency points inside the cache.
+///
+/// # Arguments
+/// * `path` - The path of the dependency, relative to the directory of the Cargo.toml file.
+/// * `cargo_toml_path` - The path to the Cargo.toml file declaring the dependency.
+/// * `cache_dir` - The absolute path to the cache directory.
+///
+/// # Returns
+/// `true` if the dependency points inside the cache.
+pub fn is_cache_path(path: &str, cargo_toml_path: &Path, cache_dir: &Path) -> bool {
+    let base = match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(cargo_toml_path),
+        Err(_) => cargo_toml_path.to_path_buf()
+    };
+    let base = base.parent().map(Path::to_path_buf).unwrap_or_default();
+    normalize(&base.join(path)).starts_with(normalize(cache_dir))
+}
+
+
+/// Adds dependency names to the ownership record of the Cargo.toml file.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file to record the managed dependencies in.
+/// * `names` - The names of the dependencies that NanoForge has injected.
+pub fn record_managed_dependencies(cargo_toml: &mut RawCargoToml, names: Vec<String>) {
+    record(cargo_toml, MANAGED_KEY, names);
+}
+
+
+/// Adds feature names to the ownership record of the Cargo.toml file.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file to record the created features in.
+/// * `names` - The names of the features that NanoForge has created.
+pub fn record_managed_features(cargo_toml: &mut RawCargoToml, names: Vec<String>) {
+    record(cargo_toml, FEATURES_KEY, names);
+}
+
+
+/// Adds names to a key of the ownership record.
+fn record(cargo_toml: &mut RawCargoToml, key: &str, names: Vec<String>) {
+    let mut managed = get_record(cargo_toml, key);
+    managed.extend(names);
+    managed.sort();
+    managed.dedup();
+    set_record(cargo_toml, key, managed);
+}
+
+
+/// Removes the ownership record from the Cargo.toml file.
+///
+/// # Notes
+/// The `nanoforge` and `metadata` tables are also removed if they end up empty so the wiped
+/// Cargo.toml file does not have any trace of NanoForge.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file to clear the ownership record from.
+pub fn clear_managed_dependencies(cargo_toml: &mut RawCargoToml) {
+    set_record(cargo_toml, MANAGED_KEY, Vec::new());
+    set_record(cargo_toml, FEATURES_KEY, Vec::new());
+}
+
+
+/// Overwrites a key of the ownership record of the Cargo.toml file.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file to write the ownership record to.
+/// * `key` - The key of the record.
+/// * `managed` - The names listed under the key.
+fn set_record(cargo_toml: &mut RawCargoToml, key: &str, managed: Vec<String>) {
+    let metadata = cargo_toml.package.metadata.get_or_insert_with(Table::new);
+    let nanoforge = metadata
+        .entry(NANOFORGE_METADATA_KEY)
+        .or_insert_with(|| Value::Table(Table::new()));
+
+    if let Value::Table(nanoforge) = nanoforge {
+        if managed.is_empty() {
+            nanoforge.remove(key);
+        } else {
+            nanoforge.insert(
+                key.to_string(),
+                Value::Array(managed.into_iter().map(Value::String).collect())
+            );
+        }
+        if nanoforge.is_empty() {
+            metadata.remove(NANOFORGE_METADATA_KEY);
+        }
+    }
+    if metadata.is_empty() {
+        cargo_toml.package.metadata = None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_record_and_clear_managed_dependencies() {
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        assert_eq!(Vec::<String>::new(), get_managed_dependencies(&cargo_toml));
+
+        record_managed_dependencies(&mut cargo_toml, vec!["nan-two".to_string(), "nan-one".to_string()]);
+        record_managed_dependencies(&mut cargo_toml, vec!["nan-one".to_string()]);
+        assert_eq!(
+            vec!["nan-one".to_string(), "nan-two".to_string()],
+            get_managed_dependencies(&cargo_toml)
+        );
+
+        clear_managed_dependencies(&mut cargo_toml);
+        assert_eq!(None, cargo_toml.package.metadata);
+    }
+
+    #[test]
+    fn test_clear_keeps_other_metadata() {
+        let mut other = Table::new();
+        other.insert("assets".to_string(), Value::String("static".to_string()));
+        let mut metadata = Table::new();
+        metadata.insert("other-tool".to_string(), Value::Table(other));
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.package.metadata = Some(metadata.clone());
+
+        record_managed_dependencies(&mut cargo_toml, vec!["nan-one".to_string()]);
+        clear_managed_dependencies(&mut cargo_toml);
+
+        assert_eq!(Some(metadata), cargo_toml.package.metadata);
+    }
+
+}
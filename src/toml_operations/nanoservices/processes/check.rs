@@ -0,0 +1,143 @@
+//! Defines the check that the `Cargo.toml` files on disk match what the config would produce.
+use toml::{Table, Value};
+
+use crate::toml_operations::nanoservices::processes::plan::{plan_config, ManifestPlan};
+use crate::toml_operations::nanoservices::resolver::read_graph;
+use crate::toml_operations::nanoservices::conflicts::check_conflicts;
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+
+
+/// Checks that all the `Cargo.toml` files are up to date with their `[nanoservices]` declarations and the cache.
+///
+/// # Notes
+/// The comparison is semantic so formatting and ordering differences are ignored. Nothing is written to disk.
+///
+/// # Arguments
+/// * `non_invasive`: If `true` the `Cargo.toml` files outside of the cache are not checked as they would not be written to.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+///
+/// # Returns
+/// An error listing all the out of date `Cargo.toml` files and entries if any are out of date.
+pub fn check_nanoservices(non_invasive: bool, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    // the versions are resolved like the config resolves them so the registries may be contacted
+    let graph = read_graph(false, config)?;
+    check_conflicts(&graph, config)?;
+    let plans = plan_config(&graph, non_invasive, config)?;
+
+    let mut out_of_date = Vec::new();
+    for plan in plans {
+        let path = plan.path.to_str().unwrap().to_string();
+        let differences = plan_differences(&plan)?;
+        if !differences.is_empty() {
+            out_of_date.push(format!("{}: {}", path, differences.join(", ")));
+        }
+    }
+
+    if out_of_date.is_empty() {
+        println!("all Cargo.toml files are up to date");
+        return Ok(())
+    }
+    Err(NanoServiceError::new(
+        format!("Cargo.toml files are out of date, run `nanoforge config`:\n{}", out_of_date.join("\n")),
+        NanoServiceErrorStatus::Unknown
+    ))
+}
+
+
+/// Gets the dotted keys of all the entries that differ between a `Cargo.toml` file on disk and its planned config.
+///
+/// # Arguments
+/// * `plan`: The planned config of the `Cargo.toml` file.
+///
+/// # Returns
+/// The dotted keys of the entries that are missing, unexpected, or different.
+pub fn plan_differences(plan: &ManifestPlan) -> Result<Vec<String>, NanoServiceError> {
+    let path = plan.path.display();
+    let current: Table = safe_eject!(
+        toml::from_str(&plan.current),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to parse Cargo.toml: {}", path)
+    )?;
+    let expected: Table = safe_eject!(
+        Table::try_from(&plan.expected),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to serialize Cargo.toml for checking: {}", path)
+    )?;
+    Ok(table_differences(&current, &expected, ""))
+}
+
+
+/// Gets the dotted keys of all the entries that differ between two TOML tables.
+///
+/// # Arguments
+/// * `current`: The table on disk.
+/// * `expected`: The table that the config would produce.
+/// * `prefix`: The dotted key of the tables being compared (empty for the root).
+///
+/// # Returns
+/// The dotted keys of the entries that are missing, unexpected, or different.
+pub fn table_differences(current: &Table, expected: &Table, prefix: &str) -> Vec<String> {
+    let mut keys: Vec<&String> = current.keys().chain(expected.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut differences = Vec::new();
+    for key in keys {
+        let dotted_key = match prefix.is_empty() {
+            true => key.to_string(),
+            false => format!("{}.{}", prefix, key)
+        };
+        match (current.get(key), expected.get(key)) {
+            (Some(Value::Table(current)), Some(Value::Table(expected))) => {
+                differences.extend(table_differences(current, expected, &dotted_key));
+            },
+            (Some(current), Some(expected)) if current == expected => (),
+            _ => differences.push(dotted_key)
+        }
+    }
+    differences
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_table_differences() {
+        let current: Table = toml::from_str(r#"
+            [dependencies]
+            tokio = "1.0.0"
+            old-nano = { path = "../old" }
+
+            [dependencies.nan-one]
+            path = "../one"
+        "#).unwrap();
+        let expected: Table = toml::from_str(r#"
+            [dependencies]
+            tokio = "1.0.0"
+            nan-one = { path = "../.nanoservices_cache/one" }
+            nan-two = { path = "../.nanoservices_cache/two" }
+        "#).unwrap();
+
+        assert_eq!(
+            vec![
+                "dependencies.nan-one.path".to_string(),
+                "dependencies.nan-two".to_string(),
+                "dependencies.old-nano".to_string()
+            ],
+            table_differences(&current, &expected, "")
+        );
+        assert_eq!(Vec::<String>::new(), table_differences(&current, &current, ""));
+    }
+
+}
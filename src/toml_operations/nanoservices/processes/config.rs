@@ -1,14 +1,11 @@
 //! Defines the processes around configuring the `Cargo.toml` files for nanoservices.
-use std::collections::HashSet;
+use crate::docker_files::cache::nanoservices_cache_dir;
+use crate::toml_operations::nanoservices::processes::plan::expected_manifest;
+use crate::toml_operations::nanoservices::resolver::{resolve_locked_graph, NanoserviceGraph};
+use crate::toml_operations::nanoservices::shim::{is_in_cache, write_shim};
+use crate::toml_operations::file_ops::write::write_toml;
 
-use crate::docker_files::cache::CACHE_NANOSERVICES_DIR;
-use crate::toml_operations::nanoservices::configure_cargo_toml::config_cargo;
-use crate::toml_operations::nanoservices::processes::get::get_nanoservices_once;
-use crate::toml_operations::nanoservices::get_all::CargoDependencies;
-use crate::toml_operations::file_ops::{
-    read::read_toml,
-    write::write_toml,
-};
+use crate::project_config::config::NanoforgeConfig;
 
 use nanoservices_utils::errors::NanoServiceError;
 
@@ -16,51 +13,69 @@ use nanoservices_utils::errors::NanoServiceError;
 /// Configures the `Cargo.toml` files for the nanoservices.
 /// 
 /// # Note
-/// The function will continue to loop until all the nanoservices have been configured.
-pub fn recursive_config_nanoservices() -> Result<(), NanoServiceError> {
-    let (toml_paths, _, cargo_dependencies) = get_nanoservices_once(
-        HashSet::new(),
-        false
-    )?;
-    config_nanoservices_once(cargo_dependencies)?;
-    let mut existing_tomls = toml_paths;
+/// Nothing is pulled, the `Cargo.toml` files inside the images that are already in the cache are configured too.
+///
+/// # Arguments
+/// * `non_invasive`: If `true` shim crates are generated in the cache instead of writing to the tracked `Cargo.toml` files.
+/// * `config`: The configuration with the cache and the active profile that selects the images of the nanoservices.
+pub fn recursive_config_nanoservices(non_invasive: bool, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let graph = resolve_locked_graph(false, config)?;
+    write_manifests(&graph, non_invasive, config)
+}
 
-    loop {
-        let (cargo_paths, _, cargo_dependencies) = get_nanoservices_once(
-            existing_tomls.clone().into_iter().collect(),
-            true
-        )?;
 
-        if &cargo_paths.len() == &0 {
-            break;
+/// Writes every `Cargo.toml` file in the nanoservice dependency graph once.
+/// 
+/// # Notes
+/// The `Cargo.toml` files are written in topological order so the `Cargo.toml` files inside an image are written
+/// before the `Cargo.toml` files depending on the image.
+///
+/// # Arguments
+/// * `graph`: The nanoservice dependency graph.
+/// * `non_invasive`: If `true` shim crates are generated for the `Cargo.toml` files outside of the cache instead of writing to them.
+/// * `config`: The configuration with the cache and the active profile that selects the images of the nanoservices.
+pub fn write_manifests(
+    graph: &NanoserviceGraph,
+    non_invasive: bool,
+    config: &NanoforgeConfig
+) -> Result<(), NanoServiceError> {
+    for manifest in graph.topological_order(config)? {
+        let path = &manifest.path;
+        // the tracked Cargo.toml files are never written in the non-invasive mode, even to wipe a managed record
+        if non_invasive && !is_in_cache(path, &config.cache_dir) {
+            if !manifest.nanoservices.is_empty() {
+                write_shim(&manifest.cargo_toml, manifest.nanoservices.clone(), nanoservices_cache_dir(config), path, config)?;
+            }
+            continue;
         }
-        for cargo_path in cargo_paths {
-            existing_tomls.push(cargo_path);
+        if let Some(cargo_toml) = expected_manifest(manifest, config)? {
+            write_toml(path, cargo_toml)?;
         }
-        config_nanoservices_once(cargo_dependencies)?;
     }
     Ok(())
-
 }
 
 
-/// Configures the `Cargo.toml` files for the nanoservices.
-/// 
-/// # Arguments
-/// * `cargo_dependencies`: A HashMap of all the dependencies found in the `Cargo.toml` files with the path to the `Cargo.toml` file
-///                         as the key.
-pub fn config_nanoservices_once(cargo_dependencies: CargoDependencies) -> Result<(), NanoServiceError> {
-    for (path, nanoservices) in cargo_dependencies {
-        // we can unwrap the `into_raw()` function because the cargo.toml will not be here if it did not have
-        // nanoservices in the contents
-        let raw_dog_cargo = read_toml(path.to_str().unwrap())?.into_raw().unwrap();
-        let raw_dog_cargo = config_cargo(
-            raw_dog_cargo, 
-            nanoservices, 
-            CACHE_NANOSERVICES_DIR.clone(),
-            path.clone()
-        )?;
-        write_toml(path.to_str().unwrap(), raw_dog_cargo)?
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::resolver::ManifestNode;
+
+    #[test]
+    fn test_write_manifests_non_invasive_skips_tracked_managed_record() {
+        let dir = std::env::temp_dir().join("nanoforge_test_write_manifests_non_invasive");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Cargo.toml");
+        std::fs::write(&path, "# tracked\n").unwrap();
+        let mut manifest = ManifestNode::fixture(path.to_str().unwrap(), &[]);
+        manifest.has_managed = true;
+        let graph = NanoserviceGraph { manifests: vec![manifest], ..NanoserviceGraph::default() };
+
+        write_manifests(&graph, true, &NanoforgeConfig::default()).unwrap();
+
+        assert_eq!("# tracked\n", std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-    Ok(())
+
 }
@@ -0,0 +1,55 @@
+//! Defines the dry run of the config and prep processes that prints what would change without writing or pulling.
+use std::collections::BTreeSet;
+
+use crate::toml_operations::nanoservices::processes::plan::plan_config;
+use crate::toml_operations::nanoservices::resolver::read_graph;
+use crate::toml_operations::nanoservices::conflicts::check_conflicts;
+use crate::toml_operations::file_ops::{
+    diff::unified_diff,
+    write::serialize_toml,
+};
+
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::errors::NanoServiceError;
+
+
+/// Prints a unified diff of every `Cargo.toml` file that the config would change and the images that would be pulled.
+///
+/// # Notes
+/// Nothing is written to disk or pulled. Because of this, nanoservices declared inside images that have not
+/// been pulled yet cannot be listed. Only the diff is printed to stdout so it can be piped or applied, the
+/// summary and the images are printed to stderr.
+///
+/// # Arguments
+/// * `pull`: If `true` the images that would be pulled in a prep are listed.
+/// * `non_invasive`: If `true` the `Cargo.toml` files outside of the cache are not diffed as they would not be written to.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+pub fn dry_run_nanoservices(pull: bool, non_invasive: bool, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    // the versions are resolved like the config resolves them so the registries may be contacted
+    let graph = read_graph(false, config)?;
+    check_conflicts(&graph, config)?;
+    let plans = plan_config(&graph, non_invasive, config)?;
+
+    let mut changed = 0;
+    for plan in plans {
+        let expected = serialize_toml(&plan.path, &plan.expected)?;
+        if let Some(diff) = unified_diff(&plan.path.display().to_string(), &plan.current, &expected) {
+            changed += 1;
+            print!("{}", diff);
+        }
+    }
+    eprintln!("{} Cargo.toml file(s) would be changed", changed);
+
+    if pull {
+        let mut images = BTreeSet::new();
+        for (_, nanoservice) in graph.all_nanoservices().into_iter().filter(|(_, nanoservice)| !nanoservice.local.unwrap_or(false)) {
+            images.extend(nanoservice.images(&config.profile)?.into_iter().cloned());
+        }
+        eprintln!("images that would be pulled:");
+        for image in images {
+            eprintln!("    {}", image);
+        }
+    }
+    Ok(())
+}
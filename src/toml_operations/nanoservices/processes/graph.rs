@@ -1,82 +1,460 @@
 //! Graphs the nanoservices in the current workspace
-use std::collections::HashMap;
-use crate::toml_operations::nanoservices::get_all::get_all_nanoservices;
-use crate::toml_operations::file_ops::find_all_cargos::find_all_cargos_interface;
-use nanoservices_utils::errors::NanoServiceError;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::toml_operations::nanoservices::dependency_graph::{DependencyGraph, GraphNode, NodeKind};
+use crate::toml_operations::nanoservices::resolver::read_graph;
+use crate::project_config::config::NanoforgeConfig;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
 use petgraph::{Graph, Directed};
 use petgraph::dot::{Dot, Config};
+use petgraph::graph::{EdgeReference, NodeIndex};
 use graphviz_rust::{
     cmd::Format,
-    exec, parse,
-    printer::PrinterContext,
+    exec_dot,
 };
 
 
-/// Goes through all the nanoservices in the current workspace and graphs them to see their
-/// dependencies.
-/// 
-/// # Notes
-/// Writes the outcome to a file called `nanoserve_dep_graph.png`
-pub fn graph_nanos() -> Result<(), NanoServiceError>{
-    let all_cargo_paths = find_all_cargos_interface(true)?;
-    let (cargo_dependencies, _) = get_all_nanoservices(all_cargo_paths)?;
+/// The file that the graph is written to if the format needs graphviz and no output is given.
+pub const DEFAULT_GRAPH_FILE: &str = "./nanoserve_dep_graph";
+
 
-    let mut graph_deps: HashMap<String, Vec<String>> = HashMap::new();
+/// The format that the graph is rendered in.
+///
+/// # Variants
+/// * `Dot` - The graphviz DOT language.
+/// * `Svg` - An SVG image rendered by the graphviz `dot` binary.
+/// * `Png` - A PNG image rendered by the graphviz `dot` binary.
+/// * `Json` - The nodes and edges as JSON.
+/// * `Mermaid` - A Mermaid flowchart that can be embedded in Markdown.
+/// * `Text` - Every node followed by the nodes it depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GraphFormat {
+    Dot,
+    Svg,
+    #[default]
+    Png,
+    Json,
+    Mermaid,
+    Text,
+}
+
+
+impl GraphFormat {
 
-    for (cargo_path, nanos) in cargo_dependencies {
-        let mut nanoservices = Vec::new();
-        for (name, _) in nanos {
-            nanoservices.push(name.replace(".nanoservices_cache/domain_services/nanoservices/", "nanoservice:"));
+    /// Checks if the format is rendered by the graphviz `dot` binary.
+    ///
+    /// # Returns
+    /// `true` for the image formats.
+    pub fn needs_graphviz(&self) -> bool {
+        matches!(self, GraphFormat::Svg | GraphFormat::Png)
+    }
+
+    /// Gets the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Svg => "svg",
+            GraphFormat::Png => "png",
+            GraphFormat::Json => "json",
+            GraphFormat::Mermaid => "mmd",
+            GraphFormat::Text => "txt",
         }
-        graph_deps.insert(
-            cargo_path.to_str().unwrap().to_string().replace(".nanoservices_cache/domain_services/nanoservices/", "nanoservice:"), 
-            nanoservices
-        );
     }
 
-    // Create a directed graph
-    let mut graph = Graph::<&str, (), Directed>::new();
+}
 
-    // This HashMap will help in quickly finding node indices
-    let mut node_indices = HashMap::new();
 
-    // Insert all nodes into the graph and save their indices
-    for (key, deps) in &graph_deps {
-        // .replace(".nanoservices_cache/domain")
-        let index = graph.add_node(key);
-        node_indices.insert(key, index);
+impl FromStr for GraphFormat {
+    type Err = NanoServiceError;
 
-        for dep in deps {
-            if !node_indices.contains_key(dep) {
-                let index = graph.add_node(dep);
-                node_indices.insert(dep, index);
-            }
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "dot" => Ok(GraphFormat::Dot),
+            "svg" => Ok(GraphFormat::Svg),
+            "png" => Ok(GraphFormat::Png),
+            "json" => Ok(GraphFormat::Json),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            "text" => Ok(GraphFormat::Text),
+            _ => Err(NanoServiceError::new(
+                format!("Invalid graph format `{}`, expected dot, svg, png, json, mermaid, or text", format),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        }
+    }
+}
+
+
+/// The style that tells apart the images that are not pulled from a registry for the build.
+///
+/// # Variants
+/// * `Local` - The image is local and never pulled.
+/// * `Missing` - The image is not cached yet.
+/// * `OfflineCached` - The cached image is used as the registries are not contacted in the offline mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeStyle {
+    Local,
+    Missing,
+    OfflineCached,
+}
+
+
+impl NodeStyle {
+
+    /// Gets the style of a node.
+    ///
+    /// # Arguments
+    /// * `dependency_graph` - The graph with the offline mode.
+    /// * `node` - The node to style.
+    ///
+    /// # Returns
+    /// The style of the node, or `None` for the default style.
+    pub fn of(dependency_graph: &DependencyGraph, node: &GraphNode) -> Option<NodeStyle> {
+        match (node.local, node.cached, dependency_graph.offline) {
+            (true, _, _) => Some(NodeStyle::Local),
+            (false, false, _) => Some(NodeStyle::Missing),
+            (false, true, true) if node.kind != NodeKind::Crate => Some(NodeStyle::OfflineCached),
+            _ => None
         }
     }
 
-    // Now, add edges according to dependencies
-    for (key, deps) in &graph_deps {
-        let to_index = node_indices[&key];
-        for dep in deps {
-            let from_index = node_indices[&dep];
-            graph.add_edge(from_index, to_index, ());
+    /// Gets the name of the style that is shown in the text graph.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeStyle::Local => "local",
+            NodeStyle::Missing => "missing",
+            NodeStyle::OfflineCached => "offline-cached",
         }
     }
 
-    // Convert to DOT format
-    let dot_output = format!("{:?}", Dot::with_config(&graph, &[Config::EdgeNoLabel]));
+}
+
+
+/// Escapes the double quotes in a label.
+fn escape(label: &str) -> String {
+    label.replace('"', "\\\"")
+}
+
 
-    let g = parse(&dot_output).unwrap();
+/// Renders the graph in the DOT language.
+///
+/// # Notes
+/// Crates are boxes, images are 3D boxes, and kernels are ellipses. Local images are dashed, images that are not cached
+/// are dotted, and the cached images used in the offline mode are filled. A cycle in the dependencies is drawn in red.
+///
+/// # Arguments
+/// * `dependency_graph` - The graph to render.
+///
+/// # Returns
+/// The DOT source of the graph.
+pub fn render_dot(dependency_graph: &DependencyGraph) -> String {
+    let mut graph = Graph::<&str, (Option<&str>, bool), Directed>::new();
+    let node_indices: Vec<NodeIndex> = dependency_graph.nodes.iter()
+        .map(|node| graph.add_node(node.label.as_str()))
+        .collect();
+    for edge in &dependency_graph.edges {
+        graph.add_edge(node_indices[edge.from], node_indices[edge.to], (edge.label.as_deref(), edge.in_cycle));
+    }
 
-    let graph_svg = exec(
-        g,
-        &mut PrinterContext::default(),
-        vec![Format::Png.into()],
-    )
-    .unwrap();
+    let edge_attributes = |_: &Graph<&str, (Option<&str>, bool), Directed>, edge: EdgeReference<(Option<&str>, bool)>| {
+        let (label, in_cycle) = edge.weight();
+        let mut attributes = Vec::new();
+        if let Some(label) = label {
+            attributes.push(format!("label=\"{}\"", escape(label)));
+        }
+        if *in_cycle {
+            attributes.push("color=red".to_string());
+        }
+        attributes.join(", ")
+    };
+    let node_attributes = |_: &Graph<&str, (Option<&str>, bool), Directed>, (index, _): (NodeIndex, &&str)| {
+        let node = &dependency_graph.nodes[index.index()];
+        // the label is written here as the default label prints the weight with `Debug`, quotes included
+        let mut attributes = vec![
+            format!("label=\"{}\"", escape(&node.label)),
+            match node.kind {
+                NodeKind::Crate => "shape=box",
+                NodeKind::Image => "shape=box3d",
+                NodeKind::Kernel => "shape=ellipse",
+            }.to_string()
+        ];
+        match NodeStyle::of(dependency_graph, node) {
+            Some(NodeStyle::Local) => attributes.push("style=dashed".to_string()),
+            Some(NodeStyle::Missing) => attributes.push("style=dotted".to_string()),
+            Some(NodeStyle::OfflineCached) => attributes.push("style=filled, fillcolor=lightgrey".to_string()),
+            None => {}
+        }
+        if node.in_cycle {
+            attributes.push("color=red, fontcolor=red".to_string());
+        }
+        attributes.join(", ")
+    };
+    let dot = Dot::with_attr_getters(&graph, &[Config::EdgeNoLabel, Config::NodeNoLabel], &edge_attributes, &node_attributes);
+    format!("{:?}", dot)
+}
 
-    // write the graph to a file
-    std::fs::write("./nanoserve_dep_graph.png", graph_svg).expect("Failed to write graph to file");
-    println!("{:?}", Dot::with_config(&graph, &[Config::EdgeNoLabel]));
+
+/// Renders the graph as a Mermaid flowchart.
+///
+/// # Notes
+/// Crates are rectangles, images are cylinders, and kernels are stadiums, styled in the same way as the DOT graph.
+///
+/// # Arguments
+/// * `dependency_graph` - The graph to render.
+///
+/// # Returns
+/// The Mermaid source of the graph.
+pub fn render_mermaid(dependency_graph: &DependencyGraph) -> String {
+    let mut lines = vec!["flowchart LR".to_string()];
+    for (index, node) in dependency_graph.nodes.iter().enumerate() {
+        let label = node.label.replace('"', "#quot;");
+        lines.push(match node.kind {
+            NodeKind::Crate => format!("    n{}[\"{}\"]", index, label),
+            NodeKind::Image => format!("    n{}[(\"{}\")]", index, label),
+            NodeKind::Kernel => format!("    n{}([\"{}\"])", index, label),
+        });
+    }
+    for edge in &dependency_graph.edges {
+        match &edge.label {
+            Some(label) => lines.push(format!("    n{} -->|\"{}\"| n{}", edge.from, label.replace('"', "#quot;"), edge.to)),
+            None => lines.push(format!("    n{} --> n{}", edge.from, edge.to))
+        }
+    }
+    for (index, node) in dependency_graph.nodes.iter().enumerate() {
+        let mut style = Vec::new();
+        match NodeStyle::of(dependency_graph, node) {
+            Some(NodeStyle::Local) => style.push("stroke-dasharray:5 5"),
+            Some(NodeStyle::Missing) => style.push("stroke-dasharray:2 2"),
+            Some(NodeStyle::OfflineCached) => style.push("fill:#ddd"),
+            None => {}
+        }
+        if node.in_cycle {
+            style.push("stroke:red,color:red");
+        }
+        if !style.is_empty() {
+            lines.push(format!("    style n{} {}", index, style.join(",")));
+        }
+    }
+    for (index, _) in dependency_graph.edges.iter().enumerate().filter(|(_, edge)| edge.in_cycle) {
+        lines.push(format!("    linkStyle {} stroke:red", index));
+    }
+    lines.join("\n") + "\n"
+}
+
+
+/// Renders the graph as plain text with every node followed by the nodes it depends on.
+///
+/// # Arguments
+/// * `dependency_graph` - The graph to render.
+///
+/// # Returns
+/// The text of the graph.
+pub fn render_text(dependency_graph: &DependencyGraph) -> String {
+    let mut text = String::new();
+    for (index, node) in dependency_graph.nodes.iter().enumerate() {
+        let dependencies = dependency_graph.dependencies(index);
+        if dependencies.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("{}\n", describe_node(dependency_graph, node)));
+        for edge in dependencies {
+            let dependency = describe_node(dependency_graph, &dependency_graph.nodes[edge.from]);
+            match &edge.label {
+                Some(label) => text.push_str(&format!("    {} via {}\n", dependency, label)),
+                None => text.push_str(&format!("    {}\n", dependency))
+            }
+        }
+    }
+    text
+}
+
+
+/// Describes a node with its kind, its style, and if it is in a cycle.
+///
+/// # Arguments
+/// * `dependency_graph` - The graph with the offline mode.
+/// * `node` - The node to describe.
+///
+/// # Returns
+/// The label of the node followed by the tags in brackets, such as `acme/one:1.0.0 [image, local]`.
+pub fn describe_node(dependency_graph: &DependencyGraph, node: &GraphNode) -> String {
+    let mut tags = vec![match node.kind {
+        NodeKind::Crate => "crate",
+        NodeKind::Image => "image",
+        NodeKind::Kernel => "kernel",
+    }];
+    tags.extend(NodeStyle::of(dependency_graph, node).map(|style| style.name()));
+    if node.in_cycle {
+        tags.push("cycle");
+    }
+    format!("{} [{}]", node.label, tags.join(", "))
+}
+
+
+/// Renders the graph in a format.
+///
+/// # Arguments
+/// * `dependency_graph` - The graph to render.
+/// * `format` - The format to render the graph in.
+///
+/// # Returns
+/// The rendered graph, or an error if an image format is requested and graphviz is not installed.
+pub fn render_graph(dependency_graph: &DependencyGraph, format: GraphFormat) -> Result<Vec<u8>, NanoServiceError> {
+    let rendered = match format {
+        GraphFormat::Dot => render_dot(dependency_graph),
+        GraphFormat::Json => safe_eject!(
+            serde_json::to_string_pretty(dependency_graph),
+            NanoServiceErrorStatus::Unknown,
+            "Failed to serialize the graph"
+        )? + "\n",
+        GraphFormat::Mermaid => render_mermaid(dependency_graph),
+        GraphFormat::Text => render_text(dependency_graph),
+        GraphFormat::Svg | GraphFormat::Png => {
+            let graphviz_format = match format {
+                GraphFormat::Svg => Format::Svg,
+                _ => Format::Png
+            };
+            return safe_eject!(
+                exec_dot(render_dot(dependency_graph), vec![graphviz_format.into()]),
+                NanoServiceErrorStatus::Unknown,
+                format!(
+                    "Failed to render the graph as {} with the graphviz `dot` binary (the dot, json, mermaid, and \
+                    text formats do not need graphviz)",
+                    format.extension()
+                )
+            )
+        }
+    };
+    Ok(rendered.into_bytes())
+}
+
+
+/// Goes through all the nanoservices in the current workspace and graphs them to see their
+/// dependencies.
+///
+/// # Notes
+/// The graph is printed if no output is given, apart from the image formats which are written to
+/// `nanoserve_dep_graph.png` or `nanoserve_dep_graph.svg`. A cycle in the dependencies is highlighted.
+///
+/// # Arguments
+/// * `format` - The format to render the graph in.
+/// * `output` - The path to write the graph to (optional).
+/// * `config` - The configuration with the active profile whose cache is graphed.
+pub fn graph_nanos(format: GraphFormat, output: Option<&Path>, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let nanoservice_graph = read_graph(true, config)?;
+    if let Some(cycle) = nanoservice_graph.find_cycle(config) {
+        eprintln!("circular nanoservice dependency: {}", cycle);
+    }
+    let dependency_graph = DependencyGraph::build(&nanoservice_graph, config)?;
+    let rendered = render_graph(&dependency_graph, format)?;
+
+    let output = match (output, format.needs_graphviz()) {
+        (Some(output), _) => output.to_path_buf(),
+        (None, true) => PathBuf::from(format!("{}.{}", DEFAULT_GRAPH_FILE, format.extension())),
+        (None, false) => {
+            print!("{}", String::from_utf8_lossy(&rendered));
+            return Ok(())
+        }
+    };
+    safe_eject!(
+        std::fs::write(&output, rendered),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to write the graph to {}", output.display())
+    )?;
+    println!("graph written to {}", output.display());
     Ok(())
 }
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::dependency_graph::GraphEdge;
+
+    fn node(id: &str, kind: NodeKind, local: bool, cached: bool, in_cycle: bool) -> GraphNode {
+        GraphNode { id: id.to_string(), kind, label: id.to_string(), local, cached, in_cycle }
+    }
+
+    fn dependency_graph() -> DependencyGraph {
+        let nodes = vec![
+            node("web", NodeKind::Crate, false, true, false),
+            node("acme/one:1.0.0", NodeKind::Image, false, true, true),
+            node("one-crate", NodeKind::Crate, false, true, true),
+            node("one-kernel", NodeKind::Kernel, true, false, false),
+        ];
+        let edges = vec![
+            GraphEdge { from: 1, to: 0, label: Some("one [json] (dev)".to_string()), in_cycle: false },
+            GraphEdge { from: 2, to: 1, label: None, in_cycle: true },
+            GraphEdge { from: 1, to: 2, label: Some("one (dev)".to_string()), in_cycle: true },
+            GraphEdge { from: 3, to: 0, label: Some("one-kernel (dev)".to_string()), in_cycle: false },
+        ];
+        let mut dependency_graph = DependencyGraph::new(nodes, edges);
+        dependency_graph.offline = true;
+        dependency_graph
+    }
+
+    #[test]
+    fn test_graph_format_from_str() {
+        assert_eq!(GraphFormat::Mermaid, "mermaid".parse().unwrap());
+        assert_eq!(
+            "Invalid graph format `jpeg`, expected dot, svg, png, json, mermaid, or text",
+            "jpeg".parse::<GraphFormat>().unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        assert_eq!(
+            "flowchart LR\n    \
+            n0[\"web\"]\n    \
+            n1[(\"acme/one:1.0.0\")]\n    \
+            n2[\"one-crate\"]\n    \
+            n3([\"one-kernel\"])\n    \
+            n1 -->|\"one [json] (dev)\"| n0\n    \
+            n2 --> n1\n    \
+            n1 -->|\"one (dev)\"| n2\n    \
+            n3 -->|\"one-kernel (dev)\"| n0\n    \
+            style n1 fill:#ddd,stroke:red,color:red\n    \
+            style n2 stroke:red,color:red\n    \
+            style n3 stroke-dasharray:5 5\n    \
+            linkStyle 1 stroke:red\n    \
+            linkStyle 2 stroke:red\n",
+            render_mermaid(&dependency_graph())
+        );
+    }
+
+    #[test]
+    fn test_render_text() {
+        assert_eq!(
+            "web [crate]\n    \
+            acme/one:1.0.0 [image, offline-cached, cycle] via one [json] (dev)\n    \
+            one-kernel [kernel, local] via one-kernel (dev)\n\
+            acme/one:1.0.0 [image, offline-cached, cycle]\n    \
+            one-crate [crate, cycle]\n\
+            one-crate [crate, cycle]\n    \
+            acme/one:1.0.0 [image, offline-cached, cycle] via one (dev)\n",
+            render_text(&dependency_graph())
+        );
+    }
+
+    #[test]
+    fn test_render_dot() {
+        let dot = render_dot(&dependency_graph());
+
+        assert_eq!(true, dot.starts_with("digraph {"));
+        assert_eq!(
+            true,
+            dot.contains("1 [ label=\"acme/one:1.0.0\", shape=box3d, style=filled, fillcolor=lightgrey, color=red, fontcolor=red]")
+        );
+        assert_eq!(true, dot.contains("3 [ label=\"one-kernel\", shape=ellipse, style=dashed]"));
+        assert_eq!(true, dot.contains("1 -> 0 [ label=\"one [json] (dev)\"]"));
+        assert_eq!(true, dot.contains("2 -> 1 [ color=red]"));
+    }
+
+}
@@ -2,5 +2,12 @@
 pub mod prep;
 pub mod graph;
 pub mod install;
-pub mod get;
 pub mod config;
+pub mod unprep;
+pub mod plan;
+pub mod dry_run;
+pub mod check;
+pub mod update;
+pub mod status;
+pub mod why;
+pub mod tree;
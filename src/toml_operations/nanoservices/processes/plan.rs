@@ -0,0 +1,99 @@
+//! Defines the planning of the `Cargo.toml` writes of a config without touching the disk.
+use std::path::PathBuf;
+
+use crate::docker_files::cache::nanoservices_cache_dir;
+use crate::toml_operations::kernel::RawCargoToml;
+use crate::toml_operations::nanoservices::configure_cargo_toml::config_cargo;
+use crate::toml_operations::nanoservices::resolver::{ManifestNode, NanoserviceGraph};
+use crate::toml_operations::nanoservices::shim::is_in_cache;
+use crate::toml_operations::nanoservices::wipe::wipe_nanoservices;
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+
+
+/// The planned outcome of configuring a `Cargo.toml` file.
+///
+/// # Fields
+/// * `path` - The path to the `Cargo.toml` file.
+/// * `current` - The contents of the `Cargo.toml` file on disk.
+/// * `expected` - The `Cargo.toml` file that the config would write.
+pub struct ManifestPlan {
+    pub path: PathBuf,
+    pub current: String,
+    pub expected: RawCargoToml,
+}
+
+
+/// Works out the `Cargo.toml` file that the config writes for a `Cargo.toml` file in the graph.
+///
+/// # Arguments
+/// * `manifest`: The `Cargo.toml` file in the graph.
+/// * `config`: The configuration with the cache and the active profile that selects the images of the nanoservices.
+///
+/// # Returns
+/// The configured `Cargo.toml` file, or `None` if the config would not write to the `Cargo.toml` file.
+pub fn expected_manifest(
+    manifest: &ManifestNode,
+    config: &NanoforgeConfig
+) -> Result<Option<RawCargoToml>, NanoServiceError> {
+    // the config only writes Cargo.toml files that declare nanoservices or had nanoservices wiped
+    if manifest.nanoservices.is_empty() && !manifest.has_managed {
+        return Ok(None)
+    }
+    // the ownership record is the basis of the cycle so the previously injected dependencies are wiped first
+    let mut cargo_toml = manifest.cargo_toml.clone();
+    wipe_nanoservices(&mut cargo_toml, &manifest.path, &config.cache_dir);
+    if manifest.nanoservices.is_empty() {
+        return Ok(Some(cargo_toml))
+    }
+    let cargo_toml = config_cargo(
+        cargo_toml,
+        manifest.nanoservices.clone(),
+        nanoservices_cache_dir(config),
+        manifest.path.clone(),
+        &config.profile
+    )?;
+    Ok(Some(cargo_toml))
+}
+
+
+/// Plans the config of the `Cargo.toml` files in memory by wiping and configuring them without writing to disk.
+///
+/// # Arguments
+/// * `graph`: The nanoservice dependency graph with the `Cargo.toml` files to plan the config for.
+/// * `non_invasive`: If `true` the `Cargo.toml` files outside of the cache are skipped as they would not be written to.
+/// * `config`: The configuration with the cache and the active profile that selects the images of the nanoservices.
+///
+/// # Returns
+/// The planned `Cargo.toml` files that the config would write in the order they would be written.
+pub fn plan_config(
+    graph: &NanoserviceGraph,
+    non_invasive: bool,
+    config: &NanoforgeConfig
+) -> Result<Vec<ManifestPlan>, NanoServiceError> {
+    let mut plans = Vec::new();
+
+    for manifest in graph.topological_order(config)? {
+        if non_invasive && !is_in_cache(&manifest.path, &config.cache_dir) {
+            continue;
+        }
+        let expected = match expected_manifest(manifest, config)? {
+            Some(expected) => expected,
+            None => continue
+        };
+        let current = safe_eject!(
+            std::fs::read_to_string(&manifest.path),
+            NanoServiceErrorStatus::Unknown,
+            format!("Failed to read Cargo.toml: {}", manifest.path.display())
+        )?;
+        plans.push(ManifestPlan { path: manifest.path.clone(), current, expected });
+    }
+    Ok(plans)
+}
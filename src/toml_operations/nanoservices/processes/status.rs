@@ -0,0 +1,107 @@
+//! Defines the status of the nanoservices that shows what is cached and which `Cargo.toml` files are out of date.
+use std::path::PathBuf;
+
+use crate::toml_operations::nanoservices::processes::check::plan_differences;
+use crate::toml_operations::nanoservices::processes::plan::plan_config;
+use crate::toml_operations::nanoservices::resolver::{image_dir, read_graph, NanoserviceGraph};
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::errors::NanoServiceError;
+
+
+/// The status of a nanoservice declared in a `Cargo.toml` file.
+///
+/// # Fields
+/// * `name` - The name of the nanoservice.
+/// * `image` - The image of the nanoservice for the active profile.
+/// * `cached` - If the image is unpacked in the cache.
+/// * `local` - If the image is local and never pulled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NanoserviceStatus {
+    pub name: String,
+    pub image: String,
+    pub cached: bool,
+    pub local: bool,
+}
+
+
+/// The status of a `Cargo.toml` file in the graph.
+///
+/// # Fields
+/// * `path` - The path to the `Cargo.toml` file.
+/// * `out_of_date` - The dotted keys of the entries that the config would change.
+/// * `nanoservices` - The status of every nanoservice declared in the `Cargo.toml` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestStatus {
+    pub path: PathBuf,
+    pub out_of_date: Vec<String>,
+    pub nanoservices: Vec<NanoserviceStatus>,
+}
+
+
+/// Works out the status of the `Cargo.toml` files in the graph that the config would write.
+///
+/// # Arguments
+/// * `graph`: The nanoservice dependency graph.
+/// * `non_invasive`: If `true` the `Cargo.toml` files outside of the cache are skipped as they would not be written to.
+/// * `config`: The configuration with the cache and the active profile that selects the images of the nanoservices.
+///
+/// # Returns
+/// The status of every `Cargo.toml` file in the order the config would write them.
+pub fn manifest_statuses(
+    graph: &NanoserviceGraph,
+    non_invasive: bool,
+    config: &NanoforgeConfig
+) -> Result<Vec<ManifestStatus>, NanoServiceError> {
+    let mut statuses = Vec::new();
+    for plan in plan_config(graph, non_invasive, config)? {
+        let mut nanoservices = Vec::new();
+        for (name, nanoservice) in graph.manifest(&plan.path).map(|manifest| &manifest.nanoservices).into_iter().flatten() {
+            let image = nanoservice.image(&config.profile)?;
+            nanoservices.push(NanoserviceStatus {
+                name: name.clone(),
+                image: image.clone(),
+                cached: image_dir(image, config).exists(),
+                local: nanoservice.local.unwrap_or(false),
+            });
+        }
+        statuses.push(ManifestStatus {
+            out_of_date: plan_differences(&plan)?,
+            path: plan.path,
+            nanoservices,
+        });
+    }
+    Ok(statuses)
+}
+
+
+/// Prints the status of every `Cargo.toml` file and nanoservice without writing or pulling anything.
+///
+/// # Arguments
+/// * `non_invasive`: If `true` the `Cargo.toml` files outside of the cache are skipped as they would not be written to.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+pub fn status_nanoservices(non_invasive: bool, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let graph = read_graph(true, config)?;
+    let statuses = manifest_statuses(&graph, non_invasive, config)?;
+
+    for status in &statuses {
+        match status.out_of_date.is_empty() {
+            true => println!("{} (up to date)", status.path.display()),
+            false => println!("{} (out of date: {})", status.path.display(), status.out_of_date.join(", "))
+        }
+        for nanoservice in &status.nanoservices {
+            let cached = match nanoservice.cached {
+                true => "cached",
+                false => "not cached"
+            };
+            let local = match nanoservice.local {
+                true => ", local",
+                false => ""
+            };
+            println!("    {} {} ({}{})", nanoservice.name, nanoservice.image, cached, local);
+        }
+    }
+    let out_of_date = statuses.iter().filter(|status| !status.out_of_date.is_empty()).count();
+    println!("{} Cargo.toml files up to date, {} out of date", statuses.len() - out_of_date, out_of_date);
+    Ok(())
+}
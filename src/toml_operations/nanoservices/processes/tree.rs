@@ -0,0 +1,322 @@
+//! Defines the tree view of the nanoservices in the same way that `cargo tree` shows the crates.
+use std::collections::HashSet;
+
+use crate::docker_files::registry::ImageReference;
+use crate::toml_operations::nanoservices::dependency_graph::{image_id, DependencyGraph, NodeKind};
+use crate::toml_operations::nanoservices::processes::graph::describe_node;
+use crate::toml_operations::nanoservices::resolver::read_graph;
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+
+
+/// The options of the tree view.
+///
+/// # Fields
+/// * `invert` - Shows what depends on an image (with or without its tag) instead of what the crates depend on (optional).
+/// * `depth` - The maximum depth of the tree, `0` only shows the roots (optional).
+/// * `duplicates` - Shows the images declared with different tags and what depends on the chosen tag.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeOptions {
+    pub invert: Option<String>,
+    pub depth: Option<usize>,
+    pub duplicates: bool,
+}
+
+
+/// Writes the children of a node in the tree.
+///
+/// # Notes
+/// A node that has already been expanded is marked with `(*)` instead of being expanded again. A node at the depth
+/// limit is not expanded so it is still expanded if it is found again higher up the tree.
+///
+/// # Arguments
+/// * `graph`: The dependency graph.
+/// * `node`: The index of the node whose children are written.
+/// * `prefix`: The prefix of the lines of the children.
+/// * `remaining_depth`: The number of levels left to write below the node (optional).
+/// * `inverted`: If the children are the nodes depending on the node rather than its dependencies.
+/// * `expanded`: The nodes that have already been expanded.
+/// * `lines`: The lines of the tree.
+fn write_children(
+    graph: &DependencyGraph,
+    node: usize,
+    prefix: &str,
+    remaining_depth: Option<usize>,
+    inverted: bool,
+    expanded: &mut HashSet<usize>,
+    lines: &mut Vec<String>
+) {
+    if remaining_depth == Some(0) {
+        return
+    }
+    let children = |node: usize| match inverted {
+        true => graph.dependents(node).into_iter().map(|edge| (edge.to, &edge.label)).collect::<Vec<_>>(),
+        false => graph.dependencies(node).into_iter().map(|edge| (edge.from, &edge.label)).collect::<Vec<_>>()
+    };
+    let edges = children(node);
+    for (index, (child, label)) in edges.iter().enumerate() {
+        let (branch, indent) = match index == edges.len() - 1 {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   ")
+        };
+        let mut line = format!("{}{}{}", prefix, branch, describe_node(graph, &graph.nodes[*child]));
+        if let Some(label) = label {
+            line.push_str(&format!(" via {}", label));
+        }
+        let has_children = !children(*child).is_empty();
+        if has_children && expanded.contains(child) {
+            lines.push(line + " (*)");
+            continue;
+        }
+        lines.push(line);
+        let child_depth = remaining_depth.map(|depth| depth - 1);
+        if has_children && child_depth != Some(0) {
+            expanded.insert(*child);
+        }
+        write_children(graph, *child, &format!("{}{}", prefix, indent), child_depth, inverted, expanded, lines);
+    }
+}
+
+
+/// Renders the images declared with different tags with what depends on the chosen tag.
+///
+/// # Arguments
+/// * `graph`: The dependency graph with the unified images.
+/// * `max_depth`: The maximum depth of what depends on the chosen tag (optional).
+///
+/// # Returns
+/// The lines of the tree.
+fn render_duplicates(graph: &DependencyGraph, max_depth: Option<usize>) -> Vec<String> {
+    if graph.unifications.is_empty() {
+        return vec!["no images are declared with different tags".to_string()]
+    }
+    let mut lines = Vec::new();
+    let mut expanded = HashSet::new();
+    for unification in &graph.unifications {
+        lines.push(unification.name.clone());
+        lines.push(format!("├── {} (chosen)", unification.chosen));
+        if let Some(chosen) = graph.position(&image_id(&unification.chosen)) {
+            expanded.insert(chosen);
+            // what depends on the chosen tag is shown the same way as an inverted tree
+            write_children(graph, chosen, "│   ", max_depth, true, &mut expanded, &mut lines);
+        }
+        for (index, (image, manifests)) in unification.replaced.iter().enumerate() {
+            let (branch, indent) = match index == unification.replaced.len() - 1 {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   ")
+            };
+            lines.push(format!("{}{} (replaced)", branch, image));
+            for (index, manifest) in manifests.iter().enumerate() {
+                let branch = match index == manifests.len() - 1 {
+                    true => "└── ",
+                    false => "├── "
+                };
+                lines.push(format!("{}{}declared in {}", indent, branch, manifest.display()));
+            }
+        }
+    }
+    lines
+}
+
+
+/// Renders the tree of the nanoservices.
+///
+/// # Notes
+/// The roots are the crates that nothing depends on, followed by the nanoservices they pull, the nanoservices those
+/// images pull in turn, and the kernels. If every crate is depended on because of a cycle, the roots are the crates
+/// of the project (the crates that are not inside an image). An inverted tree starts at every tag of an image and
+/// shows what depends on it.
+///
+/// # Arguments
+/// * `graph`: The dependency graph to render.
+/// * `options`: The options of the tree view.
+///
+/// # Returns
+/// The text of the tree, or an error if the inverted image is not in the graph.
+pub fn render_tree(graph: &DependencyGraph, options: &TreeOptions) -> Result<String, NanoServiceError> {
+    if options.duplicates {
+        return Ok(render_duplicates(graph, options.depth).join("\n") + "\n")
+    }
+    let roots: Vec<usize> = match &options.invert {
+        Some(target) => {
+            let roots: Vec<usize> = (0..graph.nodes.len()).filter(|index| {
+                let node = &graph.nodes[*index];
+                node.kind == NodeKind::Image && (node.id == *target || ImageReference::parse(&node.id).name() == *target)
+            }).collect();
+            if roots.is_empty() {
+                return Err(NanoServiceError::new(
+                    format!("The image `{}` is not in the dependency graph", target),
+                    NanoServiceErrorStatus::NotFound
+                ))
+            }
+            roots
+        },
+        None => {
+            let roots: Vec<usize> = (0..graph.nodes.len())
+                .filter(|index| graph.dependents(*index).is_empty() && !graph.dependencies(*index).is_empty())
+                .collect();
+            match roots.is_empty() {
+                true => (0..graph.nodes.len()).filter(|index| {
+                    graph.nodes[*index].kind == NodeKind::Crate
+                        && !graph.dependents(*index).iter().any(|edge| graph.nodes[edge.to].kind == NodeKind::Image)
+                }).collect(),
+                false => roots
+            }
+        }
+    };
+
+    let mut trees = Vec::new();
+    let mut expanded = HashSet::new();
+    for root in roots {
+        let mut lines = vec![describe_node(graph, &graph.nodes[root])];
+        expanded.insert(root);
+        write_children(graph, root, "", options.depth, options.invert.is_some(), &mut expanded, &mut lines);
+        trees.push(lines.join("\n") + "\n");
+    }
+    Ok(trees.join("\n"))
+}
+
+
+/// Prints the tree of the nanoservices without writing or pulling anything.
+///
+/// # Arguments
+/// * `options`: The options of the tree view.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+pub fn tree_nanos(options: &TreeOptions, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let graph = DependencyGraph::build(&read_graph(true, config)?, config)?;
+    print!("{}", render_tree(&graph, options)?);
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use crate::toml_operations::nanoservices::dependency_graph::{GraphEdge, GraphNode};
+    use crate::toml_operations::nanoservices::unify::Unification;
+
+    fn node(id: &str, kind: NodeKind) -> GraphNode {
+        GraphNode { id: id.to_string(), kind, label: id.to_string(), local: false, cached: true, in_cycle: false }
+    }
+
+    fn edge(from: usize, to: usize, label: Option<&str>) -> GraphEdge {
+        GraphEdge { from, to, label: label.map(|label| label.to_string()), in_cycle: false }
+    }
+
+    fn dependency_graph() -> DependencyGraph {
+        let nodes = vec![
+            node("web", NodeKind::Crate),
+            node("acme/a:latest", NodeKind::Image),
+            node("a", NodeKind::Crate),
+            node("acme/kernel:1.2.0", NodeKind::Image),
+            node("kernel", NodeKind::Crate),
+        ];
+        let edges = vec![
+            edge(1, 0, Some("service-a (dev)")),
+            edge(3, 0, Some("kernel (dev)")),
+            edge(2, 1, None),
+            edge(3, 2, Some("kernel (dev)")),
+            edge(4, 3, None),
+        ];
+        let mut dependency_graph = DependencyGraph::new(nodes, edges);
+        dependency_graph.unifications = vec![Unification {
+            name: "acme/kernel".to_string(),
+            chosen: "acme/kernel:1.2.0".to_string(),
+            replaced: BTreeMap::from([("acme/kernel:1.0.0".to_string(), vec![PathBuf::from("a/Cargo.toml")])]),
+        }];
+        dependency_graph
+    }
+
+    #[test]
+    fn test_render_tree() {
+        assert_eq!(
+            "web [crate]\n\
+            ├── acme/a:latest [image] via service-a (dev)\n\
+            │   └── a [crate]\n\
+            │       └── acme/kernel:1.2.0 [image] via kernel (dev)\n\
+            │           └── kernel [crate]\n\
+            └── acme/kernel:1.2.0 [image] via kernel (dev) (*)\n",
+            render_tree(&dependency_graph(), &TreeOptions::default()).unwrap()
+        );
+        assert_eq!(
+            "web [crate]\n\
+            ├── acme/a:latest [image] via service-a (dev)\n\
+            └── acme/kernel:1.2.0 [image] via kernel (dev)\n",
+            render_tree(&dependency_graph(), &TreeOptions { depth: Some(1), ..TreeOptions::default() }).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_tree_expands_nodes_found_at_the_depth_limit() {
+        assert_eq!(
+            "web [crate]\n\
+            ├── acme/a:latest [image] via service-a (dev)\n\
+            │   └── a [crate]\n\
+            │       └── acme/kernel:1.2.0 [image] via kernel (dev)\n\
+            └── acme/kernel:1.2.0 [image] via kernel (dev)\n    \
+                └── kernel [crate]\n",
+            render_tree(&dependency_graph(), &TreeOptions { depth: Some(3), ..TreeOptions::default() }).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_cyclic_tree() {
+        let nodes = vec![
+            node("web", NodeKind::Crate),
+            node("acme/a:latest", NodeKind::Image),
+            node("a", NodeKind::Crate),
+        ];
+        let edges = vec![edge(1, 0, Some("service-a (dev)")), edge(2, 1, None), edge(0, 2, None)];
+
+        assert_eq!(
+            "web [crate]\n\
+            └── acme/a:latest [image] via service-a (dev)\n    \
+                └── a [crate]\n        \
+                    └── web [crate] (*)\n",
+            render_tree(&DependencyGraph::new(nodes, edges), &TreeOptions::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_inverted_tree() {
+        let options = TreeOptions { invert: Some("acme/kernel".to_string()), ..TreeOptions::default() };
+
+        assert_eq!(
+            "acme/kernel:1.2.0 [image]\n\
+            ├── web [crate] via kernel (dev)\n\
+            └── a [crate] via kernel (dev)\n    \
+                └── acme/a:latest [image]\n        \
+                    └── web [crate] via service-a (dev)\n",
+            render_tree(&dependency_graph(), &options).unwrap()
+        );
+        assert_eq!(
+            "The image `acme/other` is not in the dependency graph",
+            render_tree(&dependency_graph(), &TreeOptions { invert: Some("acme/other".to_string()), ..options })
+                .unwrap_err()
+                .message
+        );
+    }
+
+    #[test]
+    fn test_render_duplicates() {
+        let options = TreeOptions { duplicates: true, depth: Some(1), ..TreeOptions::default() };
+
+        assert_eq!(
+            "acme/kernel\n\
+            ├── acme/kernel:1.2.0 (chosen)\n\
+            │   ├── web [crate] via kernel (dev)\n\
+            │   └── a [crate] via kernel (dev)\n\
+            └── acme/kernel:1.0.0 (replaced)\n    \
+                └── declared in a/Cargo.toml\n",
+            render_tree(&dependency_graph(), &options).unwrap()
+        );
+    }
+
+}
@@ -0,0 +1,272 @@
+//! Defines the processes around restoring the `Cargo.toml` files to their declared-only state.
+use std::path::Path;
+use toml_edit::{DocumentMut, Item};
+use crate::docker_files::cache::remove_cache;
+use crate::toml_operations::nanoservices::ownership::{is_cache_path, NANOFORGE_METADATA_KEY, MANAGED_KEY, FEATURES_KEY};
+use crate::toml_operations::file_ops::find_all_cargos::find_all_cargos_interface;
+
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+
+
+/// Removes all the NanoForge managed dependencies (including kernels) from the `Cargo.toml` files outside of the cache.
+///
+/// # Arguments
+/// * `wipe_cache`: If `true` the nanoservices cache is also deleted.
+/// * `config`: The configuration with the discovery settings and the cache directory.
+pub fn unprep_nanoservices(wipe_cache: bool, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    // the cache is not searched so the profile does not change the Cargo.toml files found
+    let cargo_paths = find_all_cargos_interface(false, config)?;
+
+    for cargo_path in cargo_paths {
+        let contents = safe_eject!(
+            std::fs::read_to_string(&cargo_path),
+            NanoServiceErrorStatus::Unknown,
+            format!("Failed to read Cargo.toml: {}", cargo_path.display())
+        )?;
+        // Cargo.toml files without managed dependencies are left untouched
+        if let Some(unprepped) = unprep_cargo_toml(&contents, &cargo_path, &config.cache_dir)? {
+            println!("unprepping: {}", cargo_path.display());
+            safe_eject!(
+                std::fs::write(&cargo_path, unprepped),
+                NanoServiceErrorStatus::Unknown,
+                format!("Failed to write Cargo.toml: {}", cargo_path.display())
+            )?;
+        }
+    }
+    if wipe_cache {
+        println!("removing the nanoservices cache");
+        remove_cache(config);
+    }
+    Ok(())
+}
+
+
+/// Finds the path dependencies of a Cargo.toml file that point inside the cache.
+///
+/// # Notes
+/// This is the fallback for the `Cargo.toml` files that were prepped before the ownership record existed.
+///
+/// # Arguments
+/// * `document`: The parsed Cargo.toml file.
+/// * `cargo_toml_path`: The path to the Cargo.toml file that the dependency paths are relative to.
+/// * `cache_dir`: The absolute path to the cache directory.
+///
+/// # Returns
+/// The names of the dependencies pointing inside the cache.
+fn cached_path_dependencies(document: &DocumentMut, cargo_toml_path: &Path, cache_dir: &Path) -> Vec<String> {
+    let dependencies = match document.get("dependencies").and_then(Item::as_table_like) {
+        Some(dependencies) => dependencies,
+        None => return Vec::new()
+    };
+    dependencies.iter()
+        .filter(|(_, item)| {
+            item.as_table_like()
+                .and_then(|table| table.get("path"))
+                .and_then(Item::as_str)
+                .map(|path| is_cache_path(path, cargo_toml_path, cache_dir))
+                .unwrap_or(false)
+        })
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+
+/// Removes the NanoForge managed dependencies from a Cargo.toml file in place.
+///
+/// # Notes
+/// The dependencies are taken from the `[package.metadata.nanoforge]` ownership record. If there is no record the
+/// path dependencies pointing inside the cache are removed instead. The `dep:` entries of the removed dependencies
+/// are removed from the features, the features that NanoForge created are removed if they end up empty, and
+/// everything else, including comments and the order of the tables, is kept.
+///
+/// # Arguments
+/// * `contents`: The contents of the Cargo.toml file to unprep.
+/// * `cargo_toml_path`: The path to the Cargo.toml file.
+/// * `cache_dir`: The absolute path to the cache directory.
+///
+/// # Returns
+/// The contents of the unprepped Cargo.toml file, or `None` if there was nothing managed by NanoForge to remove.
+pub fn unprep_cargo_toml(
+    contents: &str,
+    cargo_toml_path: &Path,
+    cache_dir: &Path
+) -> Result<Option<String>, NanoServiceError> {
+    let mut document = safe_eject!(
+        contents.parse::<DocumentMut>(),
+        NanoServiceErrorStatus::BadRequest,
+        format!("Failed to parse Cargo.toml: {}", cargo_toml_path.display())
+    )?;
+    let record = |key: &str| -> Vec<String> {
+        document.get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get(NANOFORGE_METADATA_KEY))
+            .and_then(|nanoforge| nanoforge.get(key))
+            .and_then(Item::as_array)
+            .map(|managed| managed.iter().filter_map(|name| name.as_str()).map(str::to_string).collect())
+            .unwrap_or_default()
+    };
+    let mut managed = record(MANAGED_KEY);
+    let created_features = record(FEATURES_KEY);
+    if managed.is_empty() {
+        managed = cached_path_dependencies(&document, cargo_toml_path, cache_dir);
+    }
+    if managed.is_empty() {
+        return Ok(None)
+    }
+
+    if let Some(dependencies) = document.get_mut("dependencies").and_then(Item::as_table_like_mut) {
+        for name in &managed {
+            dependencies.remove(name);
+        }
+    }
+    if let Some(features) = document.get_mut("features").and_then(Item::as_table_like_mut) {
+        let entries: Vec<String> = managed.iter().map(|name| format!("dep:{}", name)).collect();
+        for (_, enabled) in features.iter_mut() {
+            if let Some(enabled) = enabled.as_array_mut() {
+                // the new first entry takes the formatting of the old one so the array keeps its layout
+                let prefix = enabled.get(0).and_then(|item| item.decor().prefix()).cloned();
+                enabled.retain(|item| !item.as_str().map(|item| entries.iter().any(|entry| entry == item)).unwrap_or(false));
+                if let (Some(first), Some(prefix)) = (enabled.get_mut(0), prefix) {
+                    first.decor_mut().set_prefix(prefix);
+                }
+            }
+        }
+        let empty: Vec<String> = created_features.iter()
+            .filter(|name| features.get(name).and_then(Item::as_array).map(|enabled| enabled.is_empty()).unwrap_or(false))
+            .cloned()
+            .collect();
+        for name in &empty {
+            features.remove(name);
+        }
+        if !empty.is_empty() && features.is_empty() {
+            document.remove("features");
+        }
+    }
+    // the metadata tables are removed if they end up empty so the Cargo.toml file has no trace of NanoForge
+    if let Some(package) = document.get_mut("package").and_then(Item::as_table_like_mut) {
+        if let Some(metadata) = package.get_mut("metadata").and_then(Item::as_table_like_mut) {
+            if let Some(nanoforge) = metadata.get_mut(NANOFORGE_METADATA_KEY).and_then(Item::as_table_like_mut) {
+                nanoforge.remove(MANAGED_KEY);
+                nanoforge.remove(FEATURES_KEY);
+                if nanoforge.is_empty() {
+                    metadata.remove(NANOFORGE_METADATA_KEY);
+                }
+            }
+            if metadata.is_empty() {
+                package.remove("metadata");
+            }
+        }
+    }
+    Ok(Some(document.to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_unprep_cargo_toml() {
+        let contents = std::fs::read_to_string("./tests/assets/prepped_cargo.toml").unwrap();
+        let cache_dir = NanoforgeConfig::default().cache_dir;
+        let outcome = unprep_cargo_toml(&contents, &PathBuf::from("tests/assets/prepped_cargo.toml"), &cache_dir)
+            .unwrap()
+            .unwrap();
+
+        let expected = contents
+            .replace("[package.metadata.nanoforge]\nmanaged = [\"nan-one\", \"nan-one-kernel\"]\n\n", "")
+            .replace("[dependencies.nan-one]\npath = \"../.nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/.\"\n\n", "")
+            .replace("[dependencies.nan-one-kernel]\npath = \"../.nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/kernel\"\n\n", "");
+        assert_eq!(expected, outcome);
+    }
+
+    #[test]
+    fn test_unprep_cargo_toml_without_record() {
+        let contents = "[package]\n\
+            name = \"legacy\"\n\
+            version = \"0.1.0\"\n\
+            edition = \"2021\"\n\
+            \n\
+            # the web server\n\
+            [dependencies]\n\
+            tokio = \"1.0.0\"\n\
+            nan-one = { path = \".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/.\", optional = true }\n\
+            local-crate = { path = \"../local-crate\" }\n\
+            \n\
+            [features]\n\
+            with-nan-one = [\"dep:nan-one\", \"tokio/full\"]\n";
+        let cache_dir = NanoforgeConfig::default().cache_dir;
+        let outcome = unprep_cargo_toml(contents, &PathBuf::from("Cargo.toml"), &cache_dir).unwrap().unwrap();
+
+        assert_eq!(
+            "[package]\n\
+            name = \"legacy\"\n\
+            version = \"0.1.0\"\n\
+            edition = \"2021\"\n\
+            \n\
+            # the web server\n\
+            [dependencies]\n\
+            tokio = \"1.0.0\"\n\
+            local-crate = { path = \"../local-crate\" }\n\
+            \n\
+            [features]\n\
+            with-nan-one = [\"tokio/full\"]\n",
+            outcome
+        );
+    }
+
+    #[test]
+    fn test_unprep_cargo_toml_nothing_managed() {
+        let contents = std::fs::read_to_string("./tests/assets/Cargo.toml").unwrap();
+        let cache_dir = NanoforgeConfig::default().cache_dir;
+        assert_eq!(
+            None,
+            unprep_cargo_toml(&contents, &PathBuf::from("tests/assets/Cargo.toml"), &cache_dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unprep_cargo_toml_removes_created_features() {
+        let contents = "[package]\n\
+            name = \"gated\"\n\
+            version = \"0.1.0\"\n\
+            edition = \"2021\"\n\
+            \n\
+            [package.metadata.nanoforge]\n\
+            managed = [\"nan-one\"]\n\
+            features = [\"with-nan-one\"]\n\
+            \n\
+            [dependencies]\n\
+            nan-one = { path = \"../nan-one\", optional = true }\n\
+            \n\
+            [features]\n\
+            user-empty = []\n\
+            with-nan-one = [\"dep:nan-one\"]\n";
+        let cache_dir = NanoforgeConfig::default().cache_dir;
+        let outcome = unprep_cargo_toml(contents, &PathBuf::from("Cargo.toml"), &cache_dir).unwrap().unwrap();
+
+        assert_eq!(
+            "[package]\n\
+            name = \"gated\"\n\
+            version = \"0.1.0\"\n\
+            edition = \"2021\"\n\
+            \n\
+            [dependencies]\n\
+            \n\
+            [features]\n\
+            user-empty = []\n",
+            outcome
+        );
+    }
+
+}
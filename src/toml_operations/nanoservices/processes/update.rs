@@ -0,0 +1,49 @@
+//! Defines the updating of the `nanoforge.lock` file to the newest tags that satisfy the version requirements.
+use crate::lockfile::{
+    kernel::Lockfile,
+    write::write_lockfile
+};
+use crate::project_config::config::NanoforgeConfig;
+use crate::project_config::profile::Profile;
+use crate::toml_operations::file_ops::find_all_cargos::find_all_cargos_interface;
+use crate::toml_operations::file_ops::read::read_toml;
+use crate::toml_operations::nanoservices::interpolate::interpolate_nanoservice;
+use crate::toml_operations::nanoservices::validate::validate_all_nanoservices;
+use crate::toml_operations::nanoservices::version::resolve_nanoservice;
+use crate::toml_operations::nanoservices::profile::apply_profile;
+
+use nanoservices_utils::errors::NanoServiceError;
+
+
+/// Resolves the `version` of every nanoservice to the newest tag that satisfies it and rewrites the lockfile.
+///
+/// # Notes
+/// Nothing is pulled or configured, `nanoforge prep` has to be run afterwards to use the updated tags. The images
+/// of all the profiles are resolved so the lockfile works for every profile.
+///
+/// # Arguments
+/// * `config`: The configuration with the variables and the active profile whose cache is searched for nested nanoservices.
+pub fn update_nanoservices(config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let cargo_paths = find_all_cargos_interface(true, config)?;
+    validate_all_nanoservices(&cargo_paths)?;
+    // starting from an empty lockfile means nothing locked is reused
+    let mut lockfile = Lockfile::default();
+
+    for path in cargo_paths {
+        let cargo_toml = read_toml(&path)?;
+        for (name, nanoservice) in cargo_toml.nanoservices.iter().flatten() {
+            let nanoservice = interpolate_nanoservice(name, nanoservice, &config.vars, &path)?;
+            if nanoservice.version.is_none() {
+                continue;
+            }
+            for profile in nanoservice.profile_names() {
+                let profile: Profile = profile.parse()?;
+                let nanoservice = apply_profile(name, &nanoservice, &profile, &path)?;
+                let profile_config = NanoforgeConfig { profile: profile.clone(), ..config.clone() };
+                let resolved = resolve_nanoservice(name, &nanoservice, &profile_config, &mut lockfile, false, &path)?;
+                println!("{} ({}): {}", name, profile, resolved.image(&profile)?);
+            }
+        }
+    }
+    write_lockfile(&lockfile)
+}
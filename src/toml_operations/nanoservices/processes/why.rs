@@ -0,0 +1,185 @@
+//! Defines the explanation of why a nanoservice or image is in the dependency graph.
+use std::path::PathBuf;
+
+use crate::docker_files::registry::ImageReference;
+use crate::toml_operations::nanoservices::resolver::{read_graph, CycleStep, NanoserviceGraph};
+use crate::project_config::config::NanoforgeConfig;
+
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+
+
+/// Finds every chain of declarations from the `Cargo.toml` files of the project to a nanoservice, kernel, or image.
+///
+/// # Notes
+/// The target matches the name of a nanoservice or kernel, an image, or an image without its tag. Each chain starts at
+/// a `Cargo.toml` file of the project and every step after the first is declared in a `Cargo.toml` file inside the
+/// image of the step before it.
+///
+/// # Arguments
+/// * `graph`: The nanoservice dependency graph.
+/// * `target`: The nanoservice, kernel, or image to explain.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+///
+/// # Returns
+/// The chains of steps in the order the matching declarations were found.
+pub fn dependency_chains(
+    graph: &NanoserviceGraph,
+    target: &str,
+    config: &NanoforgeConfig
+) -> Result<Vec<Vec<CycleStep>>, NanoServiceError> {
+    let mut chains = Vec::new();
+    for manifest in &graph.manifests {
+        for (name, nanoservice) in &manifest.nanoservices {
+            let images = nanoservice.images(&config.profile)?;
+            let matching_image = images.iter().find(|image| {
+                image.as_str() == target || ImageReference::parse(image).name() == target
+            });
+            let matches_kernel = nanoservice.all_kernels().iter().any(|kernel| kernel.name == target);
+            let image = match matching_image {
+                Some(image) => *image,
+                None if name == target || matches_kernel => images[0],
+                None => continue
+            };
+            let step = CycleStep {
+                manifest: manifest.path.clone(),
+                nanoservice: name.clone(),
+                image: image.clone(),
+            };
+            extend_chains(graph, vec![step], config, &mut chains)?;
+        }
+    }
+    Ok(chains)
+}
+
+
+/// Extends a chain back through the declarations of the image that its first `Cargo.toml` file is inside.
+///
+/// # Arguments
+/// * `graph`: The nanoservice dependency graph.
+/// * `chain`: The steps found so far, starting with the step furthest from the target.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+/// * `chains`: The complete chains that start at a `Cargo.toml` file of the project.
+fn extend_chains(
+    graph: &NanoserviceGraph,
+    chain: Vec<CycleStep>,
+    config: &NanoforgeConfig,
+    chains: &mut Vec<Vec<CycleStep>>
+) -> Result<(), NanoServiceError> {
+    let first = &chain[0].manifest;
+    let images: Vec<&String> = graph.images.iter()
+        .filter(|(_, paths)| paths.contains(first))
+        .map(|(image, _)| image)
+        .collect();
+    if images.is_empty() {
+        chains.push(chain);
+        return Ok(())
+    }
+    for manifest in &graph.manifests {
+        // a manifest already in the chain would only lead around a cycle
+        if chain.iter().any(|step| step.manifest == manifest.path) {
+            continue;
+        }
+        for (name, nanoservice) in &manifest.nanoservices {
+            for image in nanoservice.images(&config.profile)? {
+                if !images.contains(&image) {
+                    continue;
+                }
+                let mut extended = vec![CycleStep {
+                    manifest: manifest.path.clone(),
+                    nanoservice: name.clone(),
+                    image: image.clone(),
+                }];
+                extended.extend(chain.iter().cloned());
+                extend_chains(graph, extended, config, chains)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+
+/// Formats a chain of declarations.
+///
+/// # Arguments
+/// * `chain`: The steps of the chain.
+///
+/// # Returns
+/// The `Cargo.toml` file, nanoservice, and image of every step joined with arrows.
+pub fn format_chain(chain: &[CycleStep]) -> String {
+    chain.iter()
+        .map(|step| format!("{} → {} → {}", step.manifest.display(), step.nanoservice, step.image))
+        .collect::<Vec<String>>()
+        .join(" → ")
+}
+
+
+/// Prints why a nanoservice, kernel, or image is in the dependency graph without writing or pulling anything.
+///
+/// # Arguments
+/// * `target`: The nanoservice, kernel, or image to explain.
+/// * `config`: The configuration with the active profile that selects the images of the nanoservices.
+pub fn why_nanoservice(target: &str, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+    let graph = read_graph(true, config)?;
+    let chains = dependency_chains(&graph, target, config)?;
+    if chains.is_empty() {
+        return Err(NanoServiceError::new(
+            format!("Nothing in the dependency graph depends on `{}`", target),
+            NanoServiceErrorStatus::NotFound
+        ))
+    }
+    let mut roots: Vec<&PathBuf> = chains.iter().map(|chain| &chain[0].manifest).collect();
+    roots.sort();
+    roots.dedup();
+    println!("`{}` is depended on through {} chain(s) from {} Cargo.toml file(s):", target, chains.len(), roots.len());
+    for chain in &chains {
+        println!("    {}", format_chain(chain));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::toml_operations::nanoservices::resolver::ManifestNode;
+
+    #[test]
+    fn test_dependency_chains() {
+        let graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode::fixture("Cargo.toml", &[("service-a", "acme/a", "."), ("kernel", "acme/kernel:1.2.0", ".")]),
+                ManifestNode::fixture("cache/acme_a/Cargo.toml", &[("kernel", "acme/kernel:1.2.0", ".")]),
+                ManifestNode::fixture("cache/acme_kernel_1.2.0/Cargo.toml", &[]),
+            ],
+            images: BTreeMap::from([
+                ("acme/a".to_string(), vec![PathBuf::from("cache/acme_a/Cargo.toml")]),
+                ("acme/kernel:1.2.0".to_string(), vec![PathBuf::from("cache/acme_kernel_1.2.0/Cargo.toml")]),
+            ]),
+            ..NanoserviceGraph::default()
+        };
+
+        let chains: Vec<String> = dependency_chains(&graph, "acme/kernel", &NanoforgeConfig::default())
+            .unwrap()
+            .iter()
+            .map(|chain| format_chain(chain))
+            .collect();
+
+        assert_eq!(
+            vec![
+                "Cargo.toml → kernel → acme/kernel:1.2.0",
+                "Cargo.toml → service-a → acme/a → cache/acme_a/Cargo.toml → kernel → acme/kernel:1.2.0",
+            ],
+            chains
+        );
+        assert_eq!(
+            2,
+            dependency_chains(&graph, "kernel", &NanoforgeConfig::default()).unwrap().len()
+        );
+    }
+
+}
@@ -0,0 +1,71 @@
+//! Defines the selection of the settings of a nanoservice for the active profile.
+use std::path::Path;
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+use crate::project_config::profile::Profile;
+use crate::toml_operations::nanoservices::kernel::Nanoservice;
+
+
+/// Applies the overrides of the active profile to a nanoservice and checks that it has an image for the profile.
+///
+/// # Arguments
+/// * `name` - The name of the nanoservice (used in the error).
+/// * `nanoservice` - The nanoservice to apply the profile to.
+/// * `profile` - The active profile.
+/// * `cargo_path` - The path to the Cargo.toml file declaring the nanoservice (used in the error).
+///
+/// # Returns
+/// A copy of the nanoservice with the settings of the profile.
+pub fn apply_profile(
+        name: &str,
+        nanoservice: &Nanoservice,
+        profile: &Profile,
+        cargo_path: &Path
+    ) -> Result<Nanoservice, NanoServiceError> {
+    if nanoservice.image(profile).is_err() {
+        return Err(NanoServiceError::new(
+            format!(
+                "Nanoservice `{}` in {} has no image for the `{}` profile, declared profiles: {}",
+                name, cargo_path.display(), profile, nanoservice.profile_names().join(", ")
+            ),
+            NanoServiceErrorStatus::BadRequest
+        ))
+    }
+    Ok(nanoservice.for_profile(profile))
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::file_ops::read::read_toml;
+
+    #[test]
+    fn test_apply_profile() {
+        let cargo_toml = read_toml(Path::new("./tests/assets/profiles.toml")).unwrap();
+        let nanoservices = cargo_toml.nanoservices.unwrap();
+        let nanoservice = nanoservices.get("nan-one").unwrap();
+        let path = Path::new("./tests/assets/profiles.toml");
+
+        let staging = apply_profile("nan-one", nanoservice, &"staging".parse().unwrap(), path).unwrap();
+        assert_eq!("maxwellflitton/nan-one:staging", staging.image(&"staging".parse().unwrap()).unwrap());
+        assert_eq!("staging", staging.entrypoint);
+        assert_eq!(Some(vec!["tracing".to_string()]), staging.features);
+        assert_eq!("nan-one-staging-kernel", staging.kernel.unwrap().name);
+
+        let dev = apply_profile("nan-one", nanoservice, &Profile::default(), path).unwrap();
+        assert_eq!("maxwellflitton/nan-one:dev", dev.image(&Profile::default()).unwrap());
+        assert_eq!(".", dev.entrypoint);
+        assert_eq!("nan-one-kernel", dev.kernel.unwrap().name);
+
+        let error = apply_profile("nan-one", nanoservice, &"canary".parse().unwrap(), path).unwrap_err();
+        assert_eq!(
+            "Nanoservice `nan-one` in ./tests/assets/profiles.toml has no image for the `canary` profile, declared profiles: dev, prod, staging",
+            error.message
+        );
+    }
+
+}
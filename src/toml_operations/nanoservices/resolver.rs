@@ -0,0 +1,533 @@
+//! Defines the resolver that builds the complete nanoservice dependency graph before anything is written.
+//!
+//! # Notes
+//! The graph starts at the `Cargo.toml` files of the project. Every image of their nanoservices is pulled once
+//! (if pulling) and the `Cargo.toml` files inside the unpacked image are added to the graph, until no new images are
+//! found. The `Cargo.toml` files can then be written once each in a topological order where the `Cargo.toml` files
+//! inside an image come before the `Cargo.toml` files depending on the image.
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+use crate::docker_files::download_nanoservice;
+use crate::docker_files::cache::{nanoservices_cache_dir, process_image_name, CURRENT_WORKING_DIR};
+use crate::lockfile::{
+    kernel::Lockfile,
+    read::read_lockfile,
+    write::write_lockfile
+};
+use crate::project_config::config::NanoforgeConfig;
+use crate::toml_operations::kernel::RawCargoToml;
+use crate::toml_operations::file_ops::find_all_cargos::{find_all_cargos_interface, find_nanoservice_cargos};
+use crate::toml_operations::file_ops::read::read_toml;
+use crate::toml_operations::nanoservices::kernel::Nanoservice;
+use crate::toml_operations::nanoservices::ownership::get_managed_dependencies;
+use crate::toml_operations::nanoservices::validate::validate_all_nanoservices;
+use crate::toml_operations::nanoservices::interpolate::interpolate_nanoservice;
+use crate::toml_operations::nanoservices::profile::apply_profile;
+use crate::toml_operations::nanoservices::version::resolve_nanoservice;
+use crate::toml_operations::nanoservices::conflicts::check_conflicts;
+use crate::toml_operations::nanoservices::unify::{read_unify_overrides, unify_graph, Unification};
+
+
+/// A `Cargo.toml` file in the nanoservice dependency graph.
+///
+/// # Fields
+/// * `path` - The path to the `Cargo.toml` file relative to the current directory.
+/// * `cargo_toml` - The `Cargo.toml` file as it is on disk.
+/// * `nanoservices` - The declared nanoservices after the variables, profile, and version have been resolved.
+/// * `has_managed` - If the `Cargo.toml` file has dependencies that were injected by NanoForge.
+#[derive(Debug, Clone)]
+pub struct ManifestNode {
+    pub path: PathBuf,
+    pub cargo_toml: RawCargoToml,
+    pub nanoservices: Vec<(String, Nanoservice)>,
+    pub has_managed: bool,
+}
+
+
+#[cfg(test)]
+impl ManifestNode {
+
+    /// Builds a node for the tests with the `Cargo.toml` file of the test assets.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the `Cargo.toml` file of the node.
+    /// * `nanoservices` - The name, `dev_image`, and entrypoint of every declared nanoservice.
+    ///
+    /// # Returns
+    /// The node with the nanoservices in the order they are given.
+    pub fn fixture(path: &str, nanoservices: &[(&str, &str, &str)]) -> ManifestNode {
+        ManifestNode {
+            path: PathBuf::from(path),
+            cargo_toml: read_toml(Path::new("./tests/assets/Cargo.toml")).unwrap().into_raw().unwrap(),
+            nanoservices: nanoservices.iter().map(|(name, image, entrypoint)| {
+                let nanoservice: Nanoservice = toml::from_str(
+                    &format!("dev_image = \"{}\"\nentrypoint = \"{}\"", image, entrypoint)
+                ).unwrap();
+                (name.to_string(), nanoservice)
+            }).collect(),
+            has_managed: false,
+        }
+    }
+
+}
+
+
+/// A step in a cycle of the nanoservice dependency graph.
+///
+/// # Fields
+/// * `manifest` - The path to the `Cargo.toml` file declaring the nanoservice.
+/// * `nanoservice` - The name of the nanoservice.
+/// * `image` - The image of the nanoservice that the next `Cargo.toml` file in the cycle is inside.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleStep {
+    pub manifest: PathBuf,
+    pub nanoservice: String,
+    pub image: String,
+}
+
+
+/// A cycle in the nanoservice dependency graph that ends at the `Cargo.toml` file it starts from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle(pub Vec<CycleStep>);
+
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.0 {
+            write!(f, "{} → {} → {} → ", step.manifest.display(), step.nanoservice, step.image)?;
+        }
+        match self.0.first() {
+            Some(step) => write!(f, "{}", step.manifest.display()),
+            None => Ok(())
+        }
+    }
+}
+
+
+/// The complete nanoservice dependency graph.
+///
+/// # Fields
+/// * `manifests` - The `Cargo.toml` files in the order they were found, starting with the ones of the project.
+/// * `images` - The `Cargo.toml` files inside every unpacked image, keyed by the image.
+/// * `unifications` - The images that were declared with different tags and unified to one of them.
+#[derive(Debug, Clone, Default)]
+pub struct NanoserviceGraph {
+    pub manifests: Vec<ManifestNode>,
+    pub images: BTreeMap<String, Vec<PathBuf>>,
+    pub unifications: Vec<Unification>,
+}
+
+
+impl NanoserviceGraph {
+
+    /// Gets a `Cargo.toml` file in the graph.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the `Cargo.toml` file.
+    ///
+    /// # Returns
+    /// The `Cargo.toml` file if it is in the graph.
+    pub fn manifest(&self, path: &Path) -> Option<&ManifestNode> {
+        self.manifests.iter().find(|manifest| manifest.path == path)
+    }
+
+    /// Gets the `Cargo.toml` files that a `Cargo.toml` file depends on through the images of its nanoservices.
+    ///
+    /// # Arguments
+    /// * `manifest` - The `Cargo.toml` file.
+    /// * `config` - The configuration with the active profile that selects the images.
+    ///
+    /// # Returns
+    /// The paths to the `Cargo.toml` files inside the images of the nanoservices.
+    pub fn dependencies(&self, manifest: &ManifestNode, config: &NanoforgeConfig) -> Vec<&PathBuf> {
+        let mut dependencies = Vec::new();
+        for (_, nanoservice) in &manifest.nanoservices {
+            // the images were checked when the graph was built
+            for image in nanoservice.images(&config.profile).unwrap_or_default() {
+                dependencies.extend(self.images.get(image).into_iter().flatten());
+            }
+        }
+        dependencies
+    }
+
+    /// Orders the `Cargo.toml` files so every `Cargo.toml` file comes after the `Cargo.toml` files it depends on.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration with the active profile that selects the images.
+    ///
+    /// # Returns
+    /// The `Cargo.toml` files in topological order, or an error with the cycle if there is no such order.
+    pub fn topological_order(&self, config: &NanoforgeConfig) -> Result<Vec<&ManifestNode>, NanoServiceError> {
+        self.check_cycles(config)?;
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        for manifest in &self.manifests {
+            self.visit(manifest, config, &mut visited, &mut order);
+        }
+        Ok(order)
+    }
+
+    /// Checks that there are no cycles in the graph.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration with the active profile that selects the images.
+    ///
+    /// # Returns
+    /// An error with the full path of the first cycle found if there is a cycle.
+    pub fn check_cycles(&self, config: &NanoforgeConfig) -> Result<(), NanoServiceError> {
+        match self.find_cycle(config) {
+            Some(cycle) => Err(NanoServiceError::new(
+                format!("Circular nanoservice dependency: {}", cycle),
+                NanoServiceErrorStatus::BadRequest
+            )),
+            None => Ok(())
+        }
+    }
+
+    /// Finds a cycle of `Cargo.toml` files depending on each other through the images of their nanoservices.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration with the active profile that selects the images.
+    ///
+    /// # Returns
+    /// The first cycle found, or `None` if the graph has no cycles.
+    pub fn find_cycle(&self, config: &NanoforgeConfig) -> Option<Cycle> {
+        let mut finished = HashMap::new();
+        let mut path = Vec::new();
+        self.manifests.iter().find_map(|manifest| self.find_cycle_from(manifest, config, &mut finished, &mut path))
+    }
+
+    /// Searches the `Cargo.toml` files reachable from a `Cargo.toml` file for a cycle.
+    ///
+    /// # Arguments
+    /// * `manifest` - The `Cargo.toml` file to search from.
+    /// * `config` - The configuration with the active profile that selects the images.
+    /// * `finished` - If the search from a `Cargo.toml` file has finished (`false` while it is on the path).
+    /// * `path` - The steps from the start of the search to the `Cargo.toml` file.
+    ///
+    /// # Returns
+    /// The first cycle found, or `None` if there is no cycle reachable from the `Cargo.toml` file.
+    fn find_cycle_from<'a>(
+            &'a self,
+            manifest: &'a ManifestNode,
+            config: &NanoforgeConfig,
+            finished: &mut HashMap<&'a Path, bool>,
+            path: &mut Vec<CycleStep>
+        ) -> Option<Cycle> {
+        match finished.get(manifest.path.as_path()) {
+            Some(true) => return None,
+            Some(false) => {
+                // the Cargo.toml file is on the path so the steps from it lead back to it
+                let start = path.iter().position(|step| step.manifest == manifest.path)?;
+                return Some(Cycle(path[start..].to_vec()))
+            },
+            None => {}
+        }
+        finished.insert(&manifest.path, false);
+        for (name, nanoservice) in &manifest.nanoservices {
+            for image in nanoservice.images(&config.profile).unwrap_or_default() {
+                for dependency in self.images.get(image).into_iter().flatten().filter_map(|path| self.manifest(path)) {
+                    path.push(CycleStep {
+                        manifest: manifest.path.clone(),
+                        nanoservice: name.clone(),
+                        image: image.clone(),
+                    });
+                    if let Some(cycle) = self.find_cycle_from(dependency, config, finished, path) {
+                        return Some(cycle)
+                    }
+                    path.pop();
+                }
+            }
+        }
+        finished.insert(&manifest.path, true);
+        None
+    }
+
+    /// Adds a `Cargo.toml` file to the topological order after the `Cargo.toml` files it depends on.
+    fn visit<'a>(
+            &'a self,
+            manifest: &'a ManifestNode,
+            config: &NanoforgeConfig,
+            visited: &mut HashSet<&'a Path>,
+            order: &mut Vec<&'a ManifestNode>
+        ) {
+        if !visited.insert(&manifest.path) {
+            return
+        }
+        for dependency in self.dependencies(manifest, config) {
+            if let Some(dependency) = self.manifest(dependency) {
+                self.visit(dependency, config, visited, order);
+            }
+        }
+        order.push(manifest);
+    }
+
+    /// Gets all the nanoservices declared in the graph.
+    ///
+    /// # Returns
+    /// The name and the nanoservice of every declaration.
+    pub fn all_nanoservices(&self) -> Vec<&(String, Nanoservice)> {
+        self.manifests.iter().flat_map(|manifest| manifest.nanoservices.iter()).collect()
+    }
+
+}
+
+
+/// Gets the directory that an image is unpacked into.
+///
+/// # Arguments
+/// * `image` - The image.
+/// * `config` - The configuration with the cache directory and the active profile.
+///
+/// # Returns
+/// The path to the directory relative to the current directory.
+pub fn image_dir(image: &str, config: &NanoforgeConfig) -> PathBuf {
+    let dir = nanoservices_cache_dir(config).join(process_image_name(&image.to_string()));
+    dir.strip_prefix(&*CURRENT_WORKING_DIR).map(Path::to_path_buf).unwrap_or(dir)
+}
+
+
+/// Reads a `Cargo.toml` file into a node of the graph without writing anything.
+///
+/// # Arguments
+/// * `path` - The path to the `Cargo.toml` file.
+/// * `config` - The configuration with the variables and the active profile.
+/// * `lockfile` - The lockfile to reuse the locked tags from and record the resolved tags in.
+/// * `locked` - If `true` the versions are only resolved from the lockfile and the declared tags.
+///
+/// # Returns
+/// The node, or `None` if the `Cargo.toml` file is not a package.
+fn read_manifest(
+        path: PathBuf,
+        config: &NanoforgeConfig,
+        lockfile: &mut Lockfile,
+        locked: bool
+    ) -> Result<Option<ManifestNode>, NanoServiceError> {
+    let cargo_toml = match read_toml(&path)?.into_raw() {
+        Some(raw_dog) => raw_dog,
+        None => return Ok(None)
+    };
+    let mut declared: Vec<(&String, &Nanoservice)> = cargo_toml.nanoservices.iter().flatten().collect();
+    declared.sort_by_key(|(name, _)| *name);
+
+    let mut nanoservices = Vec::new();
+    for (name, nanoservice) in declared {
+        let nanoservice = interpolate_nanoservice(name, nanoservice, &config.vars, &path)?;
+        let nanoservice = apply_profile(name, &nanoservice, &config.profile, &path)?;
+        let nanoservice = resolve_nanoservice(name, &nanoservice, config, lockfile, locked, &path)?;
+        nanoservices.push((name.clone(), nanoservice));
+    }
+    Ok(Some(ManifestNode {
+        has_managed: !get_managed_dependencies(&cargo_toml).is_empty(),
+        path,
+        cargo_toml,
+        nanoservices,
+    }))
+}
+
+
+/// Finds the `Cargo.toml` files of an image, pulling the image first if needed.
+///
+/// # Arguments
+/// * `image` - The image.
+/// * `pull` - If `true` the image is pulled, otherwise only an image already in the cache is searched.
+/// * `config` - The configuration with the discovery settings and the cache.
+///
+/// # Returns
+/// The paths to the `Cargo.toml` files of the image relative to the current directory.
+fn discover_image(image: &str, pull: bool, config: &NanoforgeConfig) -> Result<Vec<PathBuf>, NanoServiceError> {
+    if pull {
+        download_nanoservice(image, config)?;
+    }
+    let dir = image_dir(image, config);
+    let cargo_paths = match dir.exists() {
+        true => safe_eject!(
+            find_nanoservice_cargos(dir.clone(), config),
+            NanoServiceErrorStatus::Unknown,
+            format!("Failed to find the Cargo.toml files of {}", image)
+        )?,
+        false => Vec::new()
+    };
+    Ok(cargo_paths.into_iter().map(|path| dir.join(path)).collect())
+}
+
+
+/// Builds the complete nanoservice dependency graph.
+///
+/// # Notes
+/// Nothing is written to the `Cargo.toml` files. The `Cargo.toml` files found in each round are validated together
+/// before any of them are read so every problem is reported at once. Images declared with different tags are unified
+/// to one tag once the graph is complete, and a pinned tag that was not declared is then searched like any other image.
+///
+/// # Arguments
+/// * `pull` - If `true` every image (apart from `local` nanoservices) is pulled once, otherwise only the images
+///   already in the cache are searched for `Cargo.toml` files.
+/// * `locked` - If `true` the registries are never contacted, the versions are only resolved from the lockfile and
+///   the declared tags and are otherwise reported as unresolved.
+/// * `config` - The configuration with the discovery settings, the cache, and the active profile.
+/// * `lockfile` - The lockfile to reuse the locked tags from and record the resolved tags in.
+///
+/// # Returns
+/// The nanoservice dependency graph.
+pub fn resolve_graph(
+        pull: bool,
+        locked: bool,
+        config: &NanoforgeConfig,
+        lockfile: &mut Lockfile
+    ) -> Result<NanoserviceGraph, NanoServiceError> {
+    let mut graph = NanoserviceGraph::default();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut round: Vec<PathBuf> = find_all_cargos_interface(false, config)?;
+    let overrides = read_unify_overrides(&CURRENT_WORKING_DIR.join("Cargo.toml"))?;
+
+    while !round.is_empty() {
+        while !round.is_empty() {
+            round.retain(|path| seen.insert(path.clone()));
+            validate_all_nanoservices(&round)?;
+
+            let mut queue: VecDeque<PathBuf> = round.drain(..).collect();
+            while let Some(path) = queue.pop_front() {
+                let manifest = match read_manifest(path, config, lockfile, locked)? {
+                    Some(manifest) => manifest,
+                    None => continue
+                };
+                for (_, nanoservice) in &manifest.nanoservices {
+                    for image in nanoservice.images(&config.profile)? {
+                        if graph.images.contains_key(image) {
+                            continue;
+                        }
+                        let cargo_paths = discover_image(image, pull && !nanoservice.local.unwrap_or(false), config)?;
+                        round.extend(cargo_paths.iter().cloned());
+                        graph.images.insert(image.clone(), cargo_paths);
+                    }
+                }
+                graph.manifests.push(manifest);
+            }
+        }
+        let unifications = unify_graph(&mut graph, &overrides, config)?;
+        // a pinned tag that no nanoservice declared is pulled and searched like any other image
+        for unification in &unifications {
+            eprintln!("note: {}", unification);
+            if !graph.images.contains_key(&unification.chosen) {
+                let cargo_paths = discover_image(&unification.chosen, pull, config)?;
+                round.extend(cargo_paths.iter().cloned());
+                graph.images.insert(unification.chosen.clone(), cargo_paths);
+            }
+        }
+        graph.unifications.extend(unifications);
+    }
+    Ok(graph)
+}
+
+
+/// Builds the complete nanoservice dependency graph with the tags in the lockfile.
+///
+/// # Notes
+/// The lockfile is only written when a version was resolved so projects without versions do not get a lockfile.
+/// The graph is checked for cycles as cargo cannot build path dependencies that depend on each other.
+///
+/// # Arguments
+/// * `pull` - If `true` every image (apart from `local` nanoservices) is pulled once.
+/// * `config` - The configuration with the discovery settings, the cache, and the active profile.
+///
+/// # Returns
+/// The nanoservice dependency graph.
+pub fn resolve_locked_graph(pull: bool, config: &NanoforgeConfig) -> Result<NanoserviceGraph, NanoServiceError> {
+    let mut lockfile = read_lockfile()?;
+    let locked = lockfile.clone();
+    let graph = resolve_graph(pull, false, config, &mut lockfile)?;
+    graph.check_cycles(config)?;
+    check_conflicts(&graph, config)?;
+    if lockfile != locked {
+        write_lockfile(&lockfile)?;
+    }
+    Ok(graph)
+}
+
+
+/// Builds the nanoservice dependency graph for the inspection commands without any side effects.
+///
+/// # Notes
+/// No images are pulled and nothing is written, not even the lockfile, so the `graph`, `tree`, `status`, and `why`
+/// commands can be run at any time without changing the project. Only `config`, `prep`, and `install` write.
+///
+/// # Arguments
+/// * `locked` - If `true` the registries are not contacted either, versions missing from the lockfile are reported
+///   as unresolved.
+/// * `config` - The configuration with the discovery settings, the cache, and the active profile.
+///
+/// # Returns
+/// The nanoservice dependency graph of the images that are already in the cache.
+pub fn read_graph(locked: bool, config: &NanoforgeConfig) -> Result<NanoserviceGraph, NanoServiceError> {
+    resolve_graph(false, locked, config, &mut read_lockfile()?)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_topological_order() {
+        let graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode::fixture("Cargo.toml", &[("acme-one", "acme/one", "lib.rs")]),
+                ManifestNode::fixture("two/Cargo.toml", &[("acme-two", "acme/two", "lib.rs")]),
+                ManifestNode::fixture("cache/acme_one/Cargo.toml", &[("acme-two", "acme/two", "lib.rs")]),
+                ManifestNode::fixture("cache/acme_two/Cargo.toml", &[]),
+            ],
+            images: BTreeMap::from([
+                ("acme/one".to_string(), vec![PathBuf::from("cache/acme_one/Cargo.toml")]),
+                ("acme/two".to_string(), vec![PathBuf::from("cache/acme_two/Cargo.toml")]),
+            ]),
+            ..NanoserviceGraph::default()
+        };
+        let order: Vec<&Path> = graph.topological_order(&NanoforgeConfig::default())
+            .unwrap()
+            .into_iter()
+            .map(|manifest| manifest.path.as_path())
+            .collect();
+
+        assert_eq!(
+            vec![
+                Path::new("cache/acme_two/Cargo.toml"),
+                Path::new("cache/acme_one/Cargo.toml"),
+                Path::new("Cargo.toml"),
+                Path::new("two/Cargo.toml"),
+            ],
+            order
+        );
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode::fixture("Cargo.toml", &[("acme-one", "acme/one", "lib.rs")]),
+                ManifestNode::fixture("cache/acme_one/Cargo.toml", &[("acme-two", "acme/two", "lib.rs")]),
+                ManifestNode::fixture("cache/acme_two/Cargo.toml", &[("acme-one", "acme/one", "lib.rs")]),
+            ],
+            images: BTreeMap::from([
+                ("acme/one".to_string(), vec![PathBuf::from("cache/acme_one/Cargo.toml")]),
+                ("acme/two".to_string(), vec![PathBuf::from("cache/acme_two/Cargo.toml")]),
+            ]),
+            ..NanoserviceGraph::default()
+        };
+
+        assert_eq!(
+            "Circular nanoservice dependency: cache/acme_one/Cargo.toml → acme-two → acme/two → \
+            cache/acme_two/Cargo.toml → acme-one → acme/one → cache/acme_one/Cargo.toml",
+            graph.topological_order(&NanoforgeConfig::default()).unwrap_err().message
+        );
+    }
+
+}
@@ -0,0 +1,278 @@
+//! Defines the generation of shim crates for the non-invasive mode.
+//!
+//! # Notes
+//! In the non-invasive mode the `Cargo.toml` files that are tracked by version control are never written to.
+//! Instead a shim crate is generated in the cache for every `Cargo.toml` file that declares nanoservices. The
+//! shim crate depends on all the declared nanoservices and re-exports them, so the tracked `Cargo.toml` file
+//! only needs a single dependency pointing to the shim crate that never changes.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use pathdiff::diff_paths;
+use toml::Table;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+use crate::docker_files::cache::shims_cache_dir;
+use crate::toml_operations::kernel::{Package, RawCargoToml};
+use crate::toml_operations::nanoservices::kernel::{Nanoservice, DependencyEntry};
+use crate::toml_operations::nanoservices::configure_cargo_toml::config_cargo;
+use crate::toml_operations::nanoservices::ownership::clear_managed_dependencies;
+use crate::toml_operations::file_ops::write::write_toml;
+use crate::project_config::config::NanoforgeConfig;
+
+
+/// Checks if the Cargo.toml file is inside the nanoservices cache (and therefore not tracked by version control).
+///
+/// # Arguments
+/// * `cargo_toml_path` - The path to the Cargo.toml file relative to the current directory or absolute.
+/// * `cache_dir` - The absolute path to the cache directory.
+///
+/// # Returns
+/// `true` if the Cargo.toml file is inside the cache.
+pub fn is_in_cache(cargo_toml_path: &Path, cache_dir: &Path) -> bool {
+    let current_dir = match std::env::current_dir() {
+        Ok(v) => v,
+        Err(_) => return false
+    };
+    current_dir.join(cargo_toml_path).starts_with(cache_dir)
+}
+
+
+/// Gets the name of the directory of the shim crate for a Cargo.toml file.
+///
+/// # Notes
+/// The name is the directory of the Cargo.toml file with the separators replaced in the same way as the names of
+/// the image directories, so packages with the same name in different directories get their own shim crates. The
+/// Cargo.toml file in the current directory uses the name of its package.
+///
+/// # Arguments
+/// * `cargo_toml_path` - The path to the Cargo.toml file relative to the current directory or absolute.
+/// * `package_name` - The name of the package of the Cargo.toml file.
+///
+/// # Returns
+/// The name of the directory of the shim crate.
+fn shim_dir_name(cargo_toml_path: &Path, package_name: &str) -> String {
+    let directory = match std::env::current_dir() {
+        Ok(current_dir) => {
+            let path = current_dir.join(cargo_toml_path);
+            let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            parent.strip_prefix(&current_dir).map(Path::to_path_buf).unwrap_or(parent)
+        },
+        Err(_) => cargo_toml_path.parent().map(Path::to_path_buf).unwrap_or_default()
+    };
+    let name = directory.components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(part) => part.to_str(),
+            _ => None
+        })
+        .collect::<Vec<&str>>()
+        .join("_");
+    match name.is_empty() {
+        true => package_name.to_string(),
+        false => name
+    }
+}
+
+
+/// Maps the optional dependencies of the shim crate to the feature that enables them.
+///
+/// # Notes
+/// An optional nanoservice is enabled by its `feature`, or by a feature named after it if it has no `feature`. The
+/// kernels of a nanoservice with a `feature` are enabled by the same feature, and the other optional kernels by a
+/// feature named after them.
+///
+/// # Arguments
+/// * `nanos` - The nanoservices that the shim crate is built for.
+///
+/// # Returns
+/// The name of every optional dependency with the feature that enables it.
+fn feature_gates(nanos: &[(String, Nanoservice)]) -> HashMap<String, String> {
+    let mut gates = HashMap::new();
+    for (name, nanoservice) in nanos {
+        if nanoservice.is_optional() {
+            let feature = nanoservice.feature.clone().unwrap_or(name.clone());
+            gates.insert(name.clone(), feature);
+        }
+        for kernel in nanoservice.all_kernels() {
+            match &nanoservice.feature {
+                Some(feature) => { gates.insert(kernel.name.clone(), feature.clone()); },
+                None if kernel.is_optional() => { gates.insert(kernel.name.clone(), kernel.name.clone()); },
+                None => ()
+            }
+        }
+    }
+    gates
+}
+
+
+/// Generates the `lib.rs` contents of the shim crate re-exporting all the dependencies.
+///
+/// # Arguments
+/// * `shim_toml` - The configured Cargo.toml of the shim crate.
+/// * `nanos` - The nanoservices that the shim crate is built for.
+///
+/// # Returns
+/// The contents of the `lib.rs` file.
+fn generate_lib_rs(shim_toml: &RawCargoToml, nanos: &[(String, Nanoservice)]) -> String {
+    let gates = feature_gates(nanos);
+    let mut names: Vec<&String> = shim_toml.dependencies.keys().collect();
+    names.sort();
+
+    let mut lib_rs = String::from("//! Generated by NanoForge, do not edit.\n");
+    for name in names {
+        if let Some(feature) = gates.get(name) {
+            lib_rs.push_str(&format!("#[cfg(feature = \"{}\")]\n", feature));
+        }
+        lib_rs.push_str(&format!("pub use {};\n", name.replace('-', "_")));
+    }
+    lib_rs
+}
+
+
+/// Writes a shim crate into the cache for a Cargo.toml file so the Cargo.toml file does not need to be configured.
+///
+/// # Arguments
+/// * `cargo_toml` - The Cargo.toml file declaring the nanoservices (read only).
+/// * `nanos` - A vector of tuples containing the name of the nanoservice and the Nanoservice struct.
+/// * `nanoservices_path` - Where the nanoservices are (for cli-tool use `nanoservices_cache_dir(config)`).
+/// * `cargo_toml_path` - The path to the Cargo.toml file declaring the nanoservices.
+/// * `config` - The configuration with the cache directory and the active profile that selects the image of each nanoservice.
+///
+/// # Returns
+/// The path to the directory of the generated shim crate.
+pub fn write_shim(
+        cargo_toml: &RawCargoToml,
+        nanos: Vec<(String, Nanoservice)>,
+        nanoservices_path: PathBuf,
+        cargo_toml_path: &Path,
+        config: &NanoforgeConfig
+    ) -> Result<PathBuf, NanoServiceError> {
+    let shim_dir = shims_cache_dir(config).join(shim_dir_name(cargo_toml_path, &cargo_toml.package.name));
+    let shim_src_dir = shim_dir.join("src");
+    let shim_toml_path = shim_dir.join("Cargo.toml");
+    safe_eject!(
+        std::fs::create_dir_all(&shim_src_dir),
+        NanoServiceErrorStatus::Unknown,
+        "Failed to create the shim crate directory"
+    )?;
+
+    let shim_toml = RawCargoToml {
+        package: Package {
+            name: format!("{}-nanoservices", cargo_toml.package.name),
+            version: "0.1.0".to_string(),
+            edition: cargo_toml.package.edition.clone(),
+            metadata: None,
+            other: Table::new()
+        },
+        dependencies: HashMap::new(),
+        nanoservices: None,
+        features: None,
+        other: Table::new()
+    };
+    let mut shim_toml = config_cargo(
+        shim_toml,
+        nanos.clone(),
+        nanoservices_path,
+        shim_toml_path.clone(),
+        &config.profile
+    )?;
+    // the shim crate is regenerated as a whole so it does not need an ownership record, without one the
+    // shim is also left alone when the cache is scanned for nested nanoservices
+    clear_managed_dependencies(&mut shim_toml);
+    // every feature the lib.rs is gated on has to be declared or the re-export could never be enabled
+    for (dependency, feature) in feature_gates(&nanos) {
+        shim_toml.enable_dependency_in_feature(&feature, &dependency);
+    }
+    let lib_rs = generate_lib_rs(&shim_toml, &nanos);
+
+    write_toml(&shim_toml_path, shim_toml)?;
+    safe_eject!(
+        std::fs::write(shim_src_dir.join("lib.rs"), lib_rs),
+        NanoServiceErrorStatus::Unknown,
+        "Failed to write the lib.rs of the shim crate"
+    )?;
+
+    // tell the developer how to point to the shim crate (only needed once), on stderr so the output can be piped
+    let base_path = std::env::current_dir().unwrap().join(cargo_toml_path);
+    let relative_shim = match diff_paths(&shim_dir, base_path.parent().unwrap()) {
+        Some(path) => path,
+        None => shim_dir.clone()
+    };
+    eprintln!(
+        "shim generated for {:?}, depend on it with: nanoservices = {{ path = \"{}\", package = \"{}-nanoservices\" }}",
+        cargo_toml_path,
+        relative_shim.to_str().unwrap(),
+        cargo_toml.package.name
+    );
+    Ok(shim_dir)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::kernel::NanoserviceKernel;
+
+    #[test]
+    fn test_generate_lib_rs() {
+        let mut dependencies = HashMap::new();
+        dependencies.insert("nan-one".to_string(), toml::Value::Table(Table::new()));
+        dependencies.insert("nan-two".to_string(), toml::Value::Table(Table::new()));
+        let mut shim_toml = RawCargoToml::fixture("test-nanoservices");
+        shim_toml.dependencies = dependencies;
+        let mut gated = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        gated.feature = Some("with-nan-two".to_string());
+        let nanos = vec![
+            ("nan-one".to_string(), Nanoservice::fixture("maxwellflitton/nan-one", ".")),
+            ("nan-two".to_string(), gated),
+        ];
+
+        assert_eq!(
+            "//! Generated by NanoForge, do not edit.\npub use nan_one;\n#[cfg(feature = \"with-nan-two\")]\npub use nan_two;\n",
+            generate_lib_rs(&shim_toml, &nanos)
+        );
+    }
+
+    #[test]
+    fn test_feature_gates() {
+        let mut optional = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        optional.optional = Some(true);
+        let mut gated = Nanoservice::fixture("maxwellflitton/nan-one", ".");
+        gated.feature = Some("with-nan-two".to_string());
+        gated.kernel = Some(NanoserviceKernel::fixture("nan-two-kernel", "kernel"));
+        let nanos = vec![
+            ("nan-one".to_string(), optional),
+            ("nan-two".to_string(), gated),
+            ("nan-three".to_string(), Nanoservice::fixture("maxwellflitton/nan-one", ".")),
+        ];
+
+        assert_eq!(
+            HashMap::from([
+                ("nan-one".to_string(), "nan-one".to_string()),
+                ("nan-two".to_string(), "with-nan-two".to_string()),
+                ("nan-two-kernel".to_string(), "with-nan-two".to_string()),
+            ]),
+            feature_gates(&nanos)
+        );
+    }
+
+    #[test]
+    fn test_shim_dir_name() {
+        assert_eq!("web", shim_dir_name(&PathBuf::from("Cargo.toml"), "web"));
+        assert_eq!("services_web", shim_dir_name(&PathBuf::from("services/web/Cargo.toml"), "web"));
+        assert_eq!("tools_web", shim_dir_name(&PathBuf::from("./tools/web/Cargo.toml"), "web"));
+    }
+
+    #[test]
+    fn test_is_in_cache() {
+        let cache_dir = NanoforgeConfig::default().cache_dir;
+        assert_eq!(true, is_in_cache(&PathBuf::from(".nanoservices_cache/domain_services/one/Cargo.toml"), &cache_dir));
+        assert_eq!(false, is_in_cache(&PathBuf::from("one/Cargo.toml"), &cache_dir));
+    }
+
+}
@@ -0,0 +1,392 @@
+//! Defines the unification of images that are declared with different tags in the nanoservice dependency graph.
+//!
+//! # Notes
+//! If two nanoservices depend on `acme/kernel` at different tags, both copies are unpacked and cargo sees two distinct
+//! path crates with the same name, so the types do not line up across the nanoservices. Every diamond like this is
+//! unified to one tag, chosen by the `unify` policy (`highest` or `lowest`) unless the root `Cargo.toml` file pins
+//! the tag in its `[package.metadata.nanoforge.unify]` (or `[workspace.metadata.nanoforge.unify]`) table:
+//!
+//! ```toml
+//! [package.metadata.nanoforge.unify]
+//! "acme/kernel" = "1.2.0"
+//! ```
+//!
+//! Every declaration in the graph, including the ones in the `Cargo.toml` files inside the images, is rewritten to
+//! the chosen copy and the copies that are no longer depended on are dropped from the graph.
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use toml::Table;
+use nanoservices_utils::{
+    safe_eject,
+    errors::{
+        NanoServiceError,
+        NanoServiceErrorStatus
+    }
+};
+use crate::docker_files::registry::ImageReference;
+use crate::project_config::config::NanoforgeConfig;
+use crate::project_config::kernel::UnifyPolicy;
+use crate::toml_operations::nanoservices::ownership::NANOFORGE_METADATA_KEY;
+use crate::toml_operations::nanoservices::resolver::NanoserviceGraph;
+use crate::toml_operations::nanoservices::version::parse_tag;
+
+
+/// The key of the pinned tags in the NanoForge metadata table of the root `Cargo.toml` file.
+pub const UNIFY_KEY: &str = "unify";
+
+/// The tag of an image that is declared without a tag.
+const DEFAULT_TAG: &str = "latest";
+
+
+/// An image that was declared with different tags and unified to one of them.
+///
+/// # Fields
+/// * `name` - The image without the tag.
+/// * `chosen` - The image with the chosen tag that every declaration now points at.
+/// * `replaced` - The images with the other tags and the `Cargo.toml` files that declared them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Unification {
+    pub name: String,
+    pub chosen: String,
+    pub replaced: BTreeMap<String, Vec<PathBuf>>,
+}
+
+
+impl fmt::Display for Unification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let replaced: Vec<String> = self.replaced.iter().map(|(image, manifests)| {
+            let manifests: Vec<String> = manifests.iter().map(|path| path.display().to_string()).collect();
+            format!("`{}` ({})", image, manifests.join(", "))
+        }).collect();
+        write!(f, "unified `{}` to `{}` in place of {}", self.name, self.chosen, replaced.join(", "))
+    }
+}
+
+
+/// Gets the tag of an image.
+fn image_tag(image: &str) -> String {
+    ImageReference::parse(image).tag.unwrap_or_else(|| DEFAULT_TAG.to_string())
+}
+
+
+/// Reads the tags that are pinned in the root `Cargo.toml` file.
+///
+/// # Arguments
+/// * `path` - The path to the root `Cargo.toml` file.
+///
+/// # Returns
+/// The pinned tag keyed by the image without the tag (empty if the file or the table do not exist).
+pub fn read_unify_overrides(path: &Path) -> Result<BTreeMap<String, String>, NanoServiceError> {
+    if !path.exists() {
+        return Ok(BTreeMap::new())
+    }
+    let contents = safe_eject!(
+        std::fs::read_to_string(path),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to read Cargo.toml: {}", path.display())
+    )?;
+    let cargo_toml: Table = safe_eject!(
+        toml::from_str(&contents),
+        NanoServiceErrorStatus::Unknown,
+        format!("Failed to parse Cargo.toml: {}", path.display())
+    )?;
+    let unify = ["package", "workspace"].iter().find_map(|section| {
+        cargo_toml.get(*section)
+            .and_then(|section| section.get("metadata"))
+            .and_then(|metadata| metadata.get(NANOFORGE_METADATA_KEY))
+            .and_then(|nanoforge| nanoforge.get(UNIFY_KEY))
+    });
+    let mut overrides = BTreeMap::new();
+    for (name, tag) in unify.and_then(|unify| unify.as_table()).into_iter().flatten() {
+        match tag.as_str() {
+            Some(tag) => overrides.insert(name.clone(), tag.to_string()),
+            None => {
+                return Err(NanoServiceError::new(
+                    format!("The pinned tag of `{}` in {} is not a string", name, path.display()),
+                    NanoServiceErrorStatus::BadRequest
+                ))
+            }
+        };
+    }
+    Ok(overrides)
+}
+
+
+/// Checks if a tag is a valid image tag (up to 128 letters, digits, underscores, periods, and dashes that do not start
+/// with a period or a dash).
+fn is_valid_tag(tag: &str) -> bool {
+    tag.len() <= 128
+        && tag.chars().next().is_some_and(|first| first.is_ascii_alphanumeric() || first == '_')
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+
+/// Chooses the image that the declarations of an image with different tags are unified to.
+///
+/// # Notes
+/// A pinned tag does not have to be declared by any nanoservice, the image is then pulled at the pinned tag. If the
+/// image is declared both without a version (`latest`) and with versions, a warning is printed and the version is
+/// chosen by the policy.
+///
+/// # Arguments
+/// * `name` - The image without the tag.
+/// * `images` - The declared images with their tags.
+/// * `policy` - Which tag is chosen if the tag is not pinned.
+/// * `pinned` - The tag pinned in the root `Cargo.toml` file (optional).
+///
+/// # Returns
+/// The chosen image.
+pub fn choose_image(
+        name: &str,
+        images: &[&String],
+        policy: UnifyPolicy,
+        pinned: Option<&String>
+    ) -> Result<String, NanoServiceError> {
+    let tags: Vec<String> = images.iter().map(|image| image_tag(image)).collect();
+    if let Some(pinned) = pinned {
+        if let Some(index) = tags.iter().position(|tag| tag == pinned) {
+            return Ok(images[index].clone())
+        }
+        if !is_valid_tag(pinned) {
+            return Err(NanoServiceError::new(
+                format!("The pinned tag `{}` of {} is not a valid image tag", pinned, name),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        }
+        return Ok(format!("{}:{}", name, pinned))
+    }
+    let mut versions = Vec::new();
+    let mut latest = false;
+    for (image, tag) in images.iter().zip(&tags) {
+        match parse_tag(tag) {
+            Some(version) => versions.push((version, *image)),
+            None if tag == DEFAULT_TAG => latest = true,
+            None => {
+                return Err(NanoServiceError::new(
+                    format!(
+                        "Cannot unify the tags {} of {} as `{}` is not a version, pin the tag in the `{}` table of the \
+                        root Cargo.toml",
+                        tags.join(", "), name, tag, UNIFY_KEY
+                    ),
+                    NanoServiceErrorStatus::BadRequest
+                ))
+            }
+        }
+    }
+    if latest && !versions.is_empty() {
+        eprintln!(
+            "warning: {} is declared as `{}` and with the versions {}, unifying to a version, pin the tag in the `{}` \
+            table of the root Cargo.toml to choose another",
+            name, DEFAULT_TAG, tags.iter().filter(|tag| *tag != DEFAULT_TAG).cloned().collect::<Vec<String>>().join(", "),
+            UNIFY_KEY
+        );
+    }
+    let chosen = match policy {
+        UnifyPolicy::Highest => versions.into_iter().max_by(|(one, _), (two, _)| one.cmp(two)),
+        UnifyPolicy::Lowest => versions.into_iter().min_by(|(one, _), (two, _)| one.cmp(two))
+    };
+    // if nothing is a version every declaration is `latest` (the images are grouped from the declarations)
+    Ok(chosen.map(|(_, image)| image.clone()).unwrap_or_else(|| images[0].clone()))
+}
+
+
+/// Unifies every image that is declared with different tags to one tag.
+///
+/// # Arguments
+/// * `graph` - The nanoservice dependency graph to rewrite.
+/// * `overrides` - The tags pinned in the root `Cargo.toml` file keyed by the image without the tag.
+/// * `config` - The configuration with the active profile and the `unify` policy.
+///
+/// # Returns
+/// The images that were unified.
+pub fn unify_graph(
+        graph: &mut NanoserviceGraph,
+        overrides: &BTreeMap<String, String>,
+        config: &NanoforgeConfig
+    ) -> Result<Vec<Unification>, NanoServiceError> {
+    // the declared images grouped by the image without the tag
+    let mut declared: BTreeMap<String, BTreeMap<&String, Vec<PathBuf>>> = BTreeMap::new();
+    for manifest in &graph.manifests {
+        for (_, nanoservice) in &manifest.nanoservices {
+            for image in nanoservice.images(&config.profile)? {
+                let manifests = declared.entry(ImageReference::parse(image).name()).or_default()
+                    .entry(image).or_default();
+                if !manifests.contains(&manifest.path) {
+                    manifests.push(manifest.path.clone());
+                }
+            }
+        }
+    }
+
+    let mut unifications = Vec::new();
+    for (name, images) in declared {
+        if images.len() < 2 && !overrides.contains_key(&name) {
+            continue;
+        }
+        let candidates: Vec<&String> = images.keys().copied().collect();
+        let chosen = choose_image(&name, &candidates, config.unify, overrides.get(&name))?;
+        let replaced: BTreeMap<String, Vec<PathBuf>> = images.into_iter()
+            .filter(|(image, _)| **image != chosen)
+            .map(|(image, manifests)| (image.clone(), manifests))
+            .collect();
+        if !replaced.is_empty() {
+            unifications.push(Unification { name, chosen, replaced });
+        }
+    }
+    if unifications.is_empty() {
+        return Ok(unifications)
+    }
+
+    let replacements: BTreeMap<&String, &String> = unifications.iter()
+        .flat_map(|unification| unification.replaced.keys().map(move |image| (image, &unification.chosen)))
+        .collect();
+    for manifest in graph.manifests.iter_mut() {
+        for (_, nanoservice) in manifest.nanoservices.iter_mut() {
+            if let Some(chosen) = replacements.get(nanoservice.image(&config.profile)?) {
+                nanoservice.set_image(&config.profile, chosen.to_string());
+            }
+            let kernels = nanoservice.kernel.iter_mut().chain(nanoservice.kernels.iter_mut().flatten());
+            for kernel in kernels {
+                if let Some(chosen) = kernel.image.as_ref().and_then(|image| replacements.get(image)) {
+                    kernel.image = Some(chosen.to_string());
+                }
+            }
+        }
+    }
+    prune_graph(graph, config);
+    Ok(unifications)
+}
+
+
+/// Drops the `Cargo.toml` files and images that are no longer reachable from the `Cargo.toml` files of the project.
+///
+/// # Arguments
+/// * `graph` - The nanoservice dependency graph to prune.
+/// * `config` - The configuration with the active profile that selects the images.
+fn prune_graph(graph: &mut NanoserviceGraph, config: &NanoforgeConfig) {
+    let in_images: HashSet<&PathBuf> = graph.images.values().flatten().collect();
+    let mut reachable: HashSet<PathBuf> = HashSet::new();
+    let mut queue: VecDeque<&PathBuf> = graph.manifests.iter()
+        .map(|manifest| &manifest.path)
+        .filter(|path| !in_images.contains(path))
+        .collect();
+    while let Some(path) = queue.pop_front() {
+        if !reachable.insert(path.clone()) {
+            continue;
+        }
+        if let Some(manifest) = graph.manifest(path) {
+            queue.extend(graph.dependencies(manifest, config));
+        }
+    }
+
+    graph.manifests.retain(|manifest| reachable.contains(&manifest.path));
+    let referenced: HashSet<String> = graph.manifests.iter()
+        .flat_map(|manifest| manifest.nanoservices.iter())
+        .flat_map(|(_, nanoservice)| nanoservice.images(&config.profile).unwrap_or_default())
+        .cloned()
+        .collect();
+    graph.images.retain(|image, _| referenced.contains(image));
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::resolver::ManifestNode;
+
+    #[test]
+    fn test_choose_image() {
+        let one = "acme/kernel:1.0.0".to_string();
+        let two = "acme/kernel:v1.2.0".to_string();
+        let latest = "acme/kernel".to_string();
+
+        assert_eq!(two, choose_image("acme/kernel", &[&one, &two], UnifyPolicy::Highest, None).unwrap());
+        assert_eq!(one, choose_image("acme/kernel", &[&one, &two], UnifyPolicy::Lowest, None).unwrap());
+        assert_eq!(
+            latest,
+            choose_image("acme/kernel", &[&one, &latest], UnifyPolicy::Highest, Some(&"latest".to_string())).unwrap()
+        );
+        assert_eq!(one, choose_image("acme/kernel", &[&one, &latest], UnifyPolicy::Highest, None).unwrap());
+        assert_eq!(
+            "Cannot unify the tags 1.0.0, nightly of acme/kernel as `nightly` is not a version, pin the tag in the \
+            `unify` table of the root Cargo.toml",
+            choose_image(
+                "acme/kernel", &[&one, &"acme/kernel:nightly".to_string()], UnifyPolicy::Highest, None
+            ).unwrap_err().message
+        );
+        assert_eq!(
+            "acme/kernel:2.0.0",
+            choose_image("acme/kernel", &[&one, &two], UnifyPolicy::Highest, Some(&"2.0.0".to_string())).unwrap()
+        );
+        assert_eq!(
+            "The pinned tag `-2.0` of acme/kernel is not a valid image tag",
+            choose_image("acme/kernel", &[&one, &two], UnifyPolicy::Highest, Some(&"-2.0".to_string())).unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn test_unify_graph() {
+        let cache = "cache/domain_services/nanoservices";
+        let mut client = ManifestNode::fixture(&format!("{}/acme_b/Cargo.toml", cache), &[("client", "acme/client", ".")]);
+        client.nanoservices[0].1.kernel = Some(toml::from_str(
+            "name = \"kernel\"\nentrypoint = \".\"\nimage = \"acme/kernel:1.2.0\""
+        ).unwrap());
+        let mut graph = NanoserviceGraph {
+            manifests: vec![
+                ManifestNode::fixture("Cargo.toml", &[("service-a", "acme/a", "."), ("service-b", "acme/b", ".")]),
+                ManifestNode::fixture(&format!("{}/acme_a/Cargo.toml", cache), &[("kernel", "acme/kernel:1.0.0", ".")]),
+                client,
+                ManifestNode::fixture(&format!("{}/acme_kernel_1.0.0/Cargo.toml", cache), &[]),
+                ManifestNode::fixture(&format!("{}/acme_kernel_1.2.0/Cargo.toml", cache), &[]),
+            ],
+            images: BTreeMap::from([
+                ("acme/a".to_string(), vec![PathBuf::from(format!("{}/acme_a/Cargo.toml", cache))]),
+                ("acme/b".to_string(), vec![PathBuf::from(format!("{}/acme_b/Cargo.toml", cache))]),
+                ("acme/kernel:1.0.0".to_string(), vec![PathBuf::from(format!("{}/acme_kernel_1.0.0/Cargo.toml", cache))]),
+                ("acme/kernel:1.2.0".to_string(), vec![PathBuf::from(format!("{}/acme_kernel_1.2.0/Cargo.toml", cache))]),
+            ]),
+            ..NanoserviceGraph::default()
+        };
+        let config = NanoforgeConfig::default();
+
+        let unifications = unify_graph(&mut graph, &BTreeMap::new(), &config).unwrap();
+
+        assert_eq!(
+            vec![Unification {
+                name: "acme/kernel".to_string(),
+                chosen: "acme/kernel:1.2.0".to_string(),
+                replaced: BTreeMap::from([
+                    ("acme/kernel:1.0.0".to_string(), vec![PathBuf::from(format!("{}/acme_a/Cargo.toml", cache))])
+                ]),
+            }],
+            unifications
+        );
+        let (_, kernel) = &graph.manifest(Path::new(&format!("{}/acme_a/Cargo.toml", cache))).unwrap().nanoservices[0];
+        assert_eq!("acme/kernel:1.2.0", kernel.image(&config.profile).unwrap());
+        assert_eq!(None, graph.manifest(Path::new(&format!("{}/acme_kernel_1.0.0/Cargo.toml", cache))).map(|_| ()));
+        assert_eq!(
+            vec!["acme/a", "acme/b", "acme/kernel:1.2.0"],
+            graph.images.keys().collect::<Vec<&String>>()
+        );
+
+        let pinned = BTreeMap::from([("acme/kernel".to_string(), "1.0.0".to_string())]);
+        let unifications = unify_graph(&mut graph, &pinned, &config).unwrap();
+        assert_eq!("acme/kernel:1.0.0", unifications[0].chosen);
+        let (_, kernel) = &graph.manifest(Path::new(&format!("{}/acme_a/Cargo.toml", cache))).unwrap().nanoservices[0];
+        assert_eq!("acme/kernel:1.0.0", kernel.image(&config.profile).unwrap());
+        assert_eq!(vec!["acme/a", "acme/b"], graph.images.keys().collect::<Vec<&String>>());
+    }
+
+    #[test]
+    fn test_read_unify_overrides() {
+        assert_eq!(
+            BTreeMap::from([("acme/kernel".to_string(), "1.2.0".to_string())]),
+            read_unify_overrides(Path::new("./tests/assets/unify/Cargo.toml")).unwrap()
+        );
+        assert_eq!(true, read_unify_overrides(Path::new("./tests/assets/unify/missing/Cargo.toml")).unwrap().is_empty());
+    }
+
+}
@@ -0,0 +1,563 @@
+//! Defines the validation of the `[nanoservices]` section of `Cargo.toml` files.
+//!
+//! # Notes
+//! Deserializing the `[nanoservices]` section silently ignores unknown keys and stops at the first error with a
+//! generic message. The validation pass here collects every unknown key, missing required key, and invalid value
+//! with the file, line, and column so they can all be fixed in one go.
+//!
+//! The known keys, including their aliases, are read from the serde structs in `kernel.rs` so a new field is never
+//! reported as unknown. The schemas here only add the kind of value and if the key is required.
+use std::fmt;
+use std::path::PathBuf;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use toml_edit::{ImDocument, Item, Key};
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+use crate::toml_operations::nanoservices::kernel::{Nanoservice, NanoserviceKernel, ProfileOverrides};
+
+
+/// The kinds of values that a field in the `[nanoservices]` section can hold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    String,
+    Bool,
+    StringArray,
+    Table,
+    Kernel,
+    KernelList,
+    StringTable,
+    Profiles,
+}
+
+impl FieldKind {
+
+    /// Describes the kind of value for error messages.
+    ///
+    /// # Returns
+    /// The description of the kind of value.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            FieldKind::String => "a string",
+            FieldKind::Bool => "a boolean",
+            FieldKind::StringArray => "an array of strings",
+            FieldKind::Table => "a table",
+            FieldKind::Kernel => "a kernel table",
+            FieldKind::KernelList => "an array of kernel tables",
+            FieldKind::StringTable => "a table of strings",
+            FieldKind::Profiles => "a table of profile tables",
+        }
+    }
+
+    /// Checks if the item holds this kind of value.
+    ///
+    /// # Arguments
+    /// * `item` - The item to check.
+    ///
+    /// # Returns
+    /// `true` if the item holds this kind of value.
+    pub fn accepts(&self, item: &Item) -> bool {
+        match self {
+            FieldKind::String => item.is_str(),
+            FieldKind::Bool => item.is_bool(),
+            FieldKind::StringArray => match item.as_array() {
+                Some(array) => array.iter().all(|value| value.is_str()),
+                None => false
+            },
+            FieldKind::Table => item.is_table_like(),
+            FieldKind::Kernel => item.is_table_like(),
+            FieldKind::KernelList => match item {
+                Item::ArrayOfTables(_) => true,
+                _ => match item.as_array() {
+                    Some(array) => array.iter().all(|value| value.is_inline_table()),
+                    None => false
+                }
+            },
+            FieldKind::StringTable => match item.as_table_like() {
+                Some(table) => table.iter().all(|(_, value)| value.is_str()),
+                None => false
+            },
+            FieldKind::Profiles => match item.as_table_like() {
+                Some(table) => table.iter().all(|(_, value)| value.is_table_like()),
+                None => false
+            },
+        }
+    }
+
+}
+
+
+/// The schema of a field in the `[nanoservices]` section.
+///
+/// # Fields
+/// * `name` - The name of the field.
+/// * `kind` - The kind of value the field holds.
+/// * `required` - If the field has to be declared.
+pub struct Field {
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub required: bool,
+}
+
+
+/// The kinds of the fields of a nanoservice (see `Nanoservice`).
+pub const NANOSERVICE_FIELDS: &[Field] = &[
+    Field { name: "dev_image", kind: FieldKind::String, required: false },
+    Field { name: "prod_image", kind: FieldKind::String, required: false },
+    Field { name: "images", kind: FieldKind::StringTable, required: false },
+    Field { name: "entrypoint", kind: FieldKind::String, required: true },
+    Field { name: "features", kind: FieldKind::StringArray, required: false },
+    Field { name: "local", kind: FieldKind::Bool, required: false },
+    Field { name: "package", kind: FieldKind::String, required: false },
+    Field { name: "kernel", kind: FieldKind::Kernel, required: false },
+    Field { name: "kernels", kind: FieldKind::KernelList, required: false },
+    Field { name: "optional", kind: FieldKind::Bool, required: false },
+    Field { name: "feature", kind: FieldKind::String, required: false },
+    Field { name: "default_features", kind: FieldKind::Bool, required: false },
+    Field { name: "default-features", kind: FieldKind::Bool, required: false },
+    Field { name: "extra", kind: FieldKind::Table, required: false },
+    Field { name: "version", kind: FieldKind::String, required: false },
+    Field { name: "profiles", kind: FieldKind::Profiles, required: false },
+];
+
+
+/// The kinds of the fields that can be overridden for a profile of a nanoservice (see `ProfileOverrides`).
+pub const PROFILE_FIELDS: &[Field] = &[
+    Field { name: "entrypoint", kind: FieldKind::String, required: false },
+    Field { name: "features", kind: FieldKind::StringArray, required: false },
+    Field { name: "kernel", kind: FieldKind::Kernel, required: false },
+    Field { name: "kernels", kind: FieldKind::KernelList, required: false },
+];
+
+
+/// The fields that declare an image of a nanoservice, at least one of them is required.
+const IMAGE_FIELDS: &[&str] = &["dev_image", "prod_image", "images"];
+
+
+/// The kinds of the fields of a nanoservice kernel (see `NanoserviceKernel`).
+pub const KERNEL_FIELDS: &[Field] = &[
+    Field { name: "entrypoint", kind: FieldKind::String, required: true },
+    Field { name: "features", kind: FieldKind::StringArray, required: false },
+    Field { name: "package", kind: FieldKind::String, required: false },
+    Field { name: "name", kind: FieldKind::String, required: true },
+    Field { name: "optional", kind: FieldKind::Bool, required: false },
+    Field { name: "default_features", kind: FieldKind::Bool, required: false },
+    Field { name: "default-features", kind: FieldKind::Bool, required: false },
+    Field { name: "extra", kind: FieldKind::Table, required: false },
+    Field { name: "image", kind: FieldKind::String, required: false },
+];
+
+
+/// A deserializer that only records the names of the fields that a struct deserializes.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V
+        ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("the field names have been recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+
+/// Gets the keys that a serde struct accepts.
+///
+/// # Returns
+/// The names of the fields and their aliases.
+fn known_keys<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    // the deserialization always fails once the fields have been recorded
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+
+/// A problem found when validating the `[nanoservices]` section of a `Cargo.toml` file.
+///
+/// # Fields
+/// * `path` - The path to the `Cargo.toml` file.
+/// * `line` - The line of the problem (starting at 1).
+/// * `column` - The column of the problem (starting at 1).
+/// * `message` - The description of the problem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.path, self.line, self.column, self.message)
+    }
+}
+
+
+/// Validates the `[nanoservices]` sections of all the `Cargo.toml` files.
+///
+/// # Arguments
+/// * `cargo_paths` - The paths to the `Cargo.toml` files to validate.
+///
+/// # Returns
+/// An error listing every problem in every `Cargo.toml` file if there are any problems.
+pub fn validate_all_nanoservices(cargo_paths: &[PathBuf]) -> Result<(), NanoServiceError> {
+    let mut errors = Vec::new();
+    for cargo_path in cargo_paths {
+        let path = cargo_path.to_str().unwrap();
+        // unreadable files are reported by the reading of the Cargo.toml file itself
+        if let Ok(contents) = std::fs::read_to_string(cargo_path) {
+            errors.extend(validate_nanoservices(path, &contents));
+        }
+    }
+    if errors.is_empty() {
+        return Ok(())
+    }
+    Err(NanoServiceError::new(
+        format!(
+            "{} problem(s) found in the nanoservices declarations:\n{}",
+            errors.len(),
+            errors.iter().map(|error| error.to_string()).collect::<Vec<String>>().join("\n")
+        ),
+        NanoServiceErrorStatus::BadRequest
+    ))
+}
+
+
+/// Validates the `[nanoservices]` section of the contents of a `Cargo.toml` file.
+///
+/// # Arguments
+/// * `path` - The path to the `Cargo.toml` file (used in the errors).
+/// * `contents` - The contents of the `Cargo.toml` file.
+///
+/// # Returns
+/// All the problems found in the `[nanoservices]` section.
+pub fn validate_nanoservices(path: &str, contents: &str) -> Vec<ValidationError> {
+    let locate = |span: Option<std::ops::Range<usize>>, message: String| {
+        let (line, column) = line_and_column(contents, span.map(|span| span.start).unwrap_or(0));
+        ValidationError { path: path.to_string(), line, column, message }
+    };
+
+    let document = match ImDocument::parse(contents) {
+        Ok(document) => document,
+        Err(error) => {
+            return vec![locate(error.span(), format!("invalid TOML: {}", error.message()))]
+        }
+    };
+    let (nanoservices_key, nanoservices) = match document.as_table().get_key_value("nanoservices") {
+        Some(nanoservices) => nanoservices,
+        None => return Vec::new()
+    };
+    let nanoservices = match nanoservices.as_table_like() {
+        Some(nanoservices) => nanoservices,
+        None => {
+            return vec![locate(item_span(nanoservices_key, nanoservices), "`nanoservices` must be a table".to_string())]
+        }
+    };
+
+    let mut errors = Vec::new();
+    for (name, nanoservice) in nanoservices.iter() {
+        let key = nanoservices.key(name).unwrap();
+        let context = format!("nanoservice `{}`", name);
+        errors.extend(
+            validate_table(key.span(), nanoservice, known_keys::<Nanoservice>(), NANOSERVICE_FIELDS, &context)
+                .into_iter()
+                .map(|(span, message)| locate(span, message))
+        );
+        let has_image = nanoservice.as_table_like()
+            .map(|table| IMAGE_FIELDS.iter().any(|field| table.contains_key(field)))
+            .unwrap_or(true);
+        if !has_image {
+            errors.push(locate(
+                key.span(),
+                format!("missing an image in {}, declare `dev_image`, `prod_image`, or `images`", context)
+            ));
+        }
+    }
+    errors.sort_by_key(|error| (error.line, error.column));
+    errors
+}
+
+
+/// Validates a table against the schema of its fields.
+///
+/// # Arguments
+/// * `location` - The span to report problems of the table as a whole at, such as a missing required key.
+/// * `table` - The table being validated.
+/// * `known` - The keys that the table accepts.
+/// * `fields` - The kinds of the fields of the table.
+/// * `context` - The description of the table for the error messages.
+///
+/// # Returns
+/// The span and message of every problem in the table.
+fn validate_table(
+        location: Option<std::ops::Range<usize>>,
+        table: &Item,
+        known: &[&str],
+        fields: &[Field],
+        context: &str
+    ) -> Vec<(Option<std::ops::Range<usize>>, String)> {
+    let table_like = match table.as_table_like() {
+        Some(table_like) => table_like,
+        None => return vec![(table.span().or(location), format!("{} must be a table", context))]
+    };
+
+    let mut errors = Vec::new();
+    for (name, item) in table_like.iter() {
+        let key = table_like.key(name).unwrap();
+        if !known.contains(&name) {
+            let mut message = format!("unknown key `{}` in {}", name, context);
+            if let Some(suggestion) = suggest(name, known.iter().copied()) {
+                message.push_str(&format!(", did you mean `{}`?", suggestion));
+            }
+            errors.push((key.span(), message));
+            continue;
+        }
+        // a key without a kind is left for the deserialization to check
+        let field = match fields.iter().find(|field| field.name == name) {
+            Some(field) => field,
+            None => continue
+        };
+        if !field.kind.accepts(item) {
+            let mut message = format!(
+                "invalid value for `{}` in {}: expected {}, found {}",
+                name, context, field.kind.describe(), item.type_name()
+            );
+            // a close field might accept the value, such as `feature = [...]` when `features` was meant
+            let alternative = suggest(
+                name,
+                fields.iter().filter(|other| other.kind.accepts(item)).map(|other| other.name)
+            );
+            if let Some(alternative) = alternative {
+                message.push_str(&format!(", did you mean `{}`?", alternative));
+            }
+            errors.push((item_span(key, item), message));
+            continue;
+        }
+        if field.kind == FieldKind::Kernel {
+            let kernel_context = format!("kernel of {}", context);
+            errors.extend(
+                validate_table(key.span(), item, known_keys::<NanoserviceKernel>(), KERNEL_FIELDS, &kernel_context)
+            );
+        }
+        if field.kind == FieldKind::KernelList {
+            errors.extend(validate_kernel_list(key, item, context));
+        }
+        if field.kind == FieldKind::Profiles {
+            for (profile, overrides) in item.as_table_like().unwrap().iter() {
+                let profile_key = item.as_table_like().unwrap().key(profile).unwrap();
+                let profile_context = format!("profile `{}` of {}", profile, context);
+                errors.extend(validate_table(
+                    profile_key.span(), overrides, known_keys::<ProfileOverrides>(), PROFILE_FIELDS, &profile_context
+                ));
+            }
+        }
+    }
+    for field in fields.iter().filter(|field| field.required) {
+        if !table_like.contains_key(field.name) {
+            errors.push((location.clone(), format!("missing required key `{}` in {}", field.name, context)));
+        }
+    }
+    errors
+}
+
+
+/// Validates every kernel in the `kernels` of a nanoservice.
+///
+/// # Arguments
+/// * `key` - The `kernels` key.
+/// * `kernels` - The array of kernel tables, either `[[...kernels]]` tables or inline tables.
+/// * `context` - The description of the nanoservice for the error messages.
+///
+/// # Returns
+/// The span and message of every problem in the kernels.
+fn validate_kernel_list(
+        key: &Key,
+        kernels: &Item,
+        context: &str
+    ) -> Vec<(Option<std::ops::Range<usize>>, String)> {
+    let kernels: Vec<Item> = match kernels {
+        Item::ArrayOfTables(tables) => tables.iter().map(|table| Item::Table(table.clone())).collect(),
+        _ => kernels.as_array().into_iter().flatten().map(|value| Item::Value(value.clone())).collect()
+    };
+    let mut errors = Vec::new();
+    for (index, kernel) in kernels.iter().enumerate() {
+        let kernel_context = format!("kernel #{} of {}", index + 1, context);
+        errors.extend(validate_table(
+            kernel.span().or(key.span()), kernel, known_keys::<NanoserviceKernel>(), KERNEL_FIELDS, &kernel_context
+        ));
+    }
+    errors
+}
+
+
+/// Gets the span of an item, falling back to the span of its key (implicit tables do not have a span).
+fn item_span(key: &Key, item: &Item) -> Option<std::ops::Range<usize>> {
+    item.span().or(key.span())
+}
+
+
+/// Converts a byte offset into a line and column (both starting at 1).
+///
+/// # Arguments
+/// * `contents` - The contents the offset points into.
+/// * `offset` - The byte offset.
+///
+/// # Returns
+/// The line and column of the offset.
+fn line_and_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(newline) => before[newline + 1..].chars().count() + 1,
+        None => before.chars().count() + 1
+    };
+    (line, column)
+}
+
+
+/// Suggests the closest candidate to a misspelt name.
+///
+/// # Arguments
+/// * `name` - The misspelt name.
+/// * `candidates` - The valid names.
+///
+/// # Returns
+/// The closest candidate if it is close enough to be a likely typo.
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= 2.max(candidate.len() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+
+/// Calculates the Levenshtein distance between two strings.
+fn edit_distance(one: &str, two: &str) -> usize {
+    let two: Vec<char> = two.chars().collect();
+    let mut previous: Vec<usize> = (0..=two.len()).collect();
+    for (i, one_char) in one.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, two_char) in two.iter().enumerate() {
+            let substitution = previous[j] + usize::from(one_char != *two_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[two.len()]
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::toml_operations::nanoservices::kernel::Nanoservice;
+
+    #[test]
+    fn test_validate_nanoservices_valid() {
+        let contents = std::fs::read_to_string("./tests/assets/Cargo.toml").unwrap();
+        assert_eq!(
+            Vec::<ValidationError>::new(),
+            validate_nanoservices("./tests/assets/Cargo.toml", &contents)
+        );
+    }
+
+    #[test]
+    fn test_validate_nanoservices_collects_all_errors() {
+        let contents = std::fs::read_to_string("./tests/assets/invalid_nanoservices.toml").unwrap();
+        let errors: Vec<String> = validate_nanoservices("invalid_nanoservices.toml", &contents)
+            .iter()
+            .map(|error| error.to_string())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "invalid_nanoservices.toml:9:15: missing required key `entrypoint` in nanoservice `nan-one`".to_string(),
+                "invalid_nanoservices.toml:11:1: unknown key `entry_point` in nanoservice `nan-one`, did you mean `entrypoint`?".to_string(),
+                "invalid_nanoservices.toml:12:11: invalid value for `feature` in nanoservice `nan-one`: expected a string, found array, did you mean `features`?".to_string(),
+                "invalid_nanoservices.toml:13:9: invalid value for `local` in nanoservice `nan-one`: expected a boolean, found string".to_string(),
+                "invalid_nanoservices.toml:18:23: missing required key `name` in kernel of nanoservice `nan-two`".to_string(),
+                "invalid_nanoservices.toml:20:1: unknown key `nmae` in kernel of nanoservice `nan-two`, did you mean `name`?".to_string(),
+                "invalid_nanoservices.toml:29:1: missing required key `name` in kernel #2 of nanoservice `nan-three`".to_string(),
+                "invalid_nanoservices.toml:32:15: missing an image in nanoservice `nan-four`, declare `dev_image`, `prod_image`, or `images`".to_string(),
+                "invalid_nanoservices.toml:35:1: unknown key `entrypont` in profile `staging` of nanoservice `nan-four`, did you mean `entrypoint`?".to_string(),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_validate_nanoservices_default_features_spellings() {
+        let contents = "[nanoservices.nan-one]\n\
+            dev_image = \"maxwellflitton/nan-one\"\n\
+            entrypoint = \".\"\n\
+            default-features = false\n\
+            \n\
+            [nanoservices.nan-one.kernel]\n\
+            entrypoint = \"kernel\"\n\
+            name = \"nan-one-kernel\"\n\
+            default_features = false\n";
+        assert_eq!(Vec::<ValidationError>::new(), validate_nanoservices("Cargo.toml", contents));
+
+        let table: toml::Table = toml::from_str(contents).unwrap();
+        let nanoservice: Nanoservice = table["nanoservices"]["nan-one"].clone().try_into().unwrap();
+        assert_eq!(Some(false), nanoservice.default_features);
+        assert_eq!(Some(false), nanoservice.kernel.unwrap().default_features);
+    }
+
+    #[test]
+    fn test_validate_nanoservices_invalid_toml() {
+        let errors = validate_nanoservices("corrupt.toml", "sehiwofghirhngpwhfon");
+        assert_eq!(1, errors.len());
+        assert_eq!(1, errors[0].line);
+        assert_eq!(21, errors[0].column);
+    }
+
+    #[test]
+    fn test_known_keys() {
+        assert_eq!(true, known_keys::<Nanoservice>().contains(&"default-features"));
+        assert_eq!(true, known_keys::<NanoserviceKernel>().contains(&"image"));
+        assert_eq!(vec!["entrypoint", "features", "kernel", "kernels"], known_keys::<ProfileOverrides>().to_vec());
+        // every key has a kind so its value is checked
+        let schemas = [
+            (known_keys::<Nanoservice>(), NANOSERVICE_FIELDS),
+            (known_keys::<NanoserviceKernel>(), KERNEL_FIELDS),
+            (known_keys::<ProfileOverrides>(), PROFILE_FIELDS),
+        ];
+        for (known, fields) in schemas {
+            for key in known {
+                assert_eq!(true, fields.iter().any(|field| field.name == *key), "no kind for `{}`", key);
+            }
+        }
+        for field in IMAGE_FIELDS {
+            assert_eq!(true, known_keys::<Nanoservice>().contains(field));
+        }
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(Some("entrypoint"), suggest("entry_point", NANOSERVICE_FIELDS.iter().map(|f| f.name)));
+        assert_eq!(Some("dev_image"), suggest("dev-image", NANOSERVICE_FIELDS.iter().map(|f| f.name)));
+        assert_eq!(None, suggest("registry", NANOSERVICE_FIELDS.iter().map(|f| f.name)));
+    }
+
+}
@@ -0,0 +1,263 @@
+//! Defines the resolution of the `version` requirement of a nanoservice to an image tag.
+//!
+//! # Notes
+//! A nanoservice with `version = "^1.4"` gets the highest tag of its images that is compatible with the requirement,
+//! in the same way that Cargo picks a crate version. Tags can be plain versions (`1.4.3`) or prefixed with a `v`
+//! (`v1.4.3`), other tags are ignored. The resolved tags are recorded in the lockfile and reused while they still
+//! satisfy the requirement so builds are reproducible until the lockfile is updated.
+use std::path::Path;
+use semver::{Version, VersionReq};
+use nanoservices_utils::errors::{
+    NanoServiceError,
+    NanoServiceErrorStatus
+};
+use crate::docker_files::registry::{ImageReference, list_image_tags, mirror_image};
+use crate::lockfile::kernel::Lockfile;
+use crate::toml_operations::nanoservices::kernel::Nanoservice;
+use crate::project_config::config::NanoforgeConfig;
+
+
+/// The tag reported for an image whose version requirement cannot be resolved without contacting a registry.
+pub const UNRESOLVED_TAG: &str = "unresolved";
+
+
+/// Parses a tag as a version.
+///
+/// # Arguments
+/// * `tag` - The tag to parse.
+///
+/// # Returns
+/// The version of the tag if the tag is a version.
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+
+/// Selects the highest tag that satisfies a version requirement.
+///
+/// # Arguments
+/// * `tags` - The tags to select from.
+/// * `requirement` - The version requirement.
+///
+/// # Returns
+/// The highest tag satisfying the requirement if there is one.
+pub fn select_tag(tags: &[String], requirement: &VersionReq) -> Option<String> {
+    tags.iter()
+        .filter_map(|tag| parse_tag(tag).map(|version| (version, tag)))
+        .filter(|(version, _)| requirement.matches(version))
+        .max_by(|(one, _), (two, _)| one.cmp(two))
+        .map(|(_, tag)| tag.clone())
+}
+
+
+/// Resolves an image to the tag that satisfies a version requirement.
+///
+/// # Notes
+/// The tags are listed from the mirror of the registry of the image if there is one. In the offline mode the
+/// registries are not contacted so the lockfile has to have a tag that satisfies the requirement. In the locked mode
+/// the registries are not contacted either, the declared tag is used if it satisfies the requirement and otherwise
+/// the image is reported as unresolved.
+///
+/// # Arguments
+/// * `image` - The image to resolve (any tag in the image is replaced).
+/// * `requirement` - The version requirement.
+/// * `lockfile` - The lockfile to reuse the locked tag from and record the resolved tag in.
+/// * `locked` - If `true` the image is only resolved from the lockfile and the declared tag.
+/// * `config` - The configuration with the registry mirrors and the offline mode.
+///
+/// # Returns
+/// The image with the resolved tag, or with the `unresolved` tag in the locked mode.
+pub fn resolve_image(
+        image: &str,
+        requirement: &VersionReq,
+        lockfile: &mut Lockfile,
+        locked: bool,
+        config: &NanoforgeConfig
+    ) -> Result<String, NanoServiceError> {
+    let reference = ImageReference::parse(image);
+    let name = reference.name();
+    let version = requirement.to_string();
+    let satisfies = |tag: &String| parse_tag(tag).map(|tag| requirement.matches(&tag)).unwrap_or(false);
+
+    // the locked tag is reused as long as it still satisfies the requirement
+    let locked_tag = lockfile.get(&name, &version).filter(|tag| satisfies(tag)).cloned();
+    let tag = match locked_tag {
+        Some(tag) => tag,
+        None if locked => match reference.tag.filter(satisfies) {
+            Some(tag) => tag,
+            None => {
+                eprintln!(
+                    "note: {} is unresolved as no locked tag satisfies the version requirement `{}`, run `nanoforge \
+                    update` to resolve it",
+                    name, version
+                );
+                UNRESOLVED_TAG.to_string()
+            }
+        },
+        None if config.offline => {
+            return Err(NanoServiceError::new(
+                format!(
+                    "No locked tag of {} satisfies the version requirement `{}` and the registry cannot be contacted offline",
+                    name, version
+                ),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        },
+        None => {
+            let tags = list_image_tags(&mirror_image(&name, &config.mirrors))?;
+            let tag = match select_tag(&tags, requirement) {
+                Some(tag) => tag,
+                None => {
+                    return Err(NanoServiceError::new(
+                        format!("No tag of {} satisfies the version requirement `{}`", name, version),
+                        NanoServiceErrorStatus::BadRequest
+                    ))
+                }
+            };
+            lockfile.insert(&name, &version, &tag);
+            tag
+        }
+    };
+    Ok(format!("{}:{}", name, tag))
+}
+
+
+/// Resolves the image of a nanoservice for a profile to the tag that satisfies its `version` requirement.
+///
+/// # Notes
+/// Only the image of the active profile is resolved so developers do not need access to the registries of the
+/// production images. The kernels packaged in their own image are resolved against the same requirement.
+///
+/// # Arguments
+/// * `name` - The name of the nanoservice (used in the error).
+/// * `nanoservice` - The nanoservice to resolve the image of.
+/// * `config` - The configuration with the active profile.
+/// * `lockfile` - The lockfile to reuse the locked tags from and record the resolved tags in.
+/// * `locked` - If `true` the image is only resolved from the lockfile and the declared tag.
+/// * `cargo_path` - The path to the Cargo.toml file declaring the nanoservice (used in the error).
+///
+/// # Returns
+/// A copy of the nanoservice with the resolved images, or the nanoservice untouched if it has no `version`.
+pub fn resolve_nanoservice(
+        name: &str,
+        nanoservice: &Nanoservice,
+        config: &NanoforgeConfig,
+        lockfile: &mut Lockfile,
+        locked: bool,
+        cargo_path: &Path
+    ) -> Result<Nanoservice, NanoServiceError> {
+    let mut nanoservice = nanoservice.clone();
+    let version = match &nanoservice.version {
+        Some(version) => version,
+        None => return Ok(nanoservice)
+    };
+    let requirement = match VersionReq::parse(version) {
+        Ok(requirement) => requirement,
+        Err(error) => {
+            return Err(NanoServiceError::new(
+                format!(
+                    "Invalid version requirement `{}` of nanoservice `{}` in {}: {}",
+                    version, name, cargo_path.display(), error
+                ),
+                NanoServiceErrorStatus::BadRequest
+            ))
+        }
+    };
+    let image = resolve_image(nanoservice.image(&config.profile)?, &requirement, lockfile, locked, config)?;
+    nanoservice.set_image(&config.profile, image);
+    for kernel in nanoservice.kernel.iter_mut().chain(nanoservice.kernels.iter_mut().flatten()) {
+        if let Some(image) = &kernel.image {
+            kernel.image = Some(resolve_image(image, &requirement, lockfile, locked, config)?);
+        }
+    }
+    Ok(nanoservice)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_select_tag() {
+        let tags: Vec<String> = vec!["latest", "1.3.9", "v1.4.0", "1.4.3", "1.10.0", "2.0.0", "1.5.0-beta.1"]
+            .into_iter()
+            .map(|tag| tag.to_string())
+            .collect();
+
+        assert_eq!(Some("1.10.0".to_string()), select_tag(&tags, &VersionReq::parse("^1.4").unwrap()));
+        assert_eq!(Some("1.4.3".to_string()), select_tag(&tags, &VersionReq::parse("~1.4").unwrap()));
+        assert_eq!(Some("v1.4.0".to_string()), select_tag(&tags, &VersionReq::parse("=1.4.0").unwrap()));
+        assert_eq!(None, select_tag(&tags, &VersionReq::parse("^3").unwrap()));
+    }
+
+    #[test]
+    fn test_resolve_image_uses_lockfile() {
+        let mut lockfile = Lockfile::default();
+        lockfile.insert("maxwellflitton/nan-one", "^1.4", "1.4.3");
+
+        assert_eq!(
+            "maxwellflitton/nan-one:1.4.3",
+            resolve_image(
+                "maxwellflitton/nan-one:latest",
+                &VersionReq::parse("^1.4").unwrap(),
+                &mut lockfile,
+                false,
+                &NanoforgeConfig::default()
+            ).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_image_offline_without_lockfile() {
+        let config = NanoforgeConfig {
+            offline: true,
+            ..NanoforgeConfig::default()
+        };
+
+        assert_eq!(
+            "No locked tag of maxwellflitton/nan-one satisfies the version requirement `^1.4` and the registry cannot be contacted offline",
+            resolve_image(
+                "maxwellflitton/nan-one:latest",
+                &VersionReq::parse("^1.4").unwrap(),
+                &mut Lockfile::default(),
+                false,
+                &config
+            ).unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn test_resolve_image_locked_without_lockfile() {
+        let requirement = VersionReq::parse("^1.4").unwrap();
+        let config = NanoforgeConfig::default();
+
+        assert_eq!(
+            "maxwellflitton/nan-one:1.4.3",
+            resolve_image("maxwellflitton/nan-one:1.4.3", &requirement, &mut Lockfile::default(), true, &config).unwrap()
+        );
+        assert_eq!(
+            "maxwellflitton/nan-one:unresolved",
+            resolve_image("maxwellflitton/nan-one:latest", &requirement, &mut Lockfile::default(), true, &config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_nanoservice_resolves_kernel_images() {
+        let nanoservice: Nanoservice = toml::from_str(
+            "dev_image = \"acme/nan-one\"\nentrypoint = \".\"\nversion = \"^1.4\"\n\
+             kernel = { name = \"kernel\", entrypoint = \".\", image = \"acme/kernel\" }"
+        ).unwrap();
+        let mut lockfile = Lockfile::default();
+        lockfile.insert("acme/nan-one", "^1.4", "1.4.3");
+        lockfile.insert("acme/kernel", "^1.4", "1.5.0");
+        let config = NanoforgeConfig::default();
+
+        let resolved = resolve_nanoservice("nan-one", &nanoservice, &config, &mut lockfile, false, Path::new("Cargo.toml")).unwrap();
+
+        assert_eq!("acme/nan-one:1.4.3", resolved.image(&config.profile).unwrap());
+        assert_eq!(Some("acme/kernel:1.5.0".to_string()), resolved.kernel.unwrap().image);
+    }
+
+}
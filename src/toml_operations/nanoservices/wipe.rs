@@ -1,33 +1,52 @@
-use toml::Value;
+use std::path::Path;
 use crate::toml_operations::kernel::RawCargoToml;
+use crate::toml_operations::nanoservices::ownership::{
+    get_managed_dependencies,
+    get_managed_features,
+    clear_managed_dependencies,
+    is_cache_path
+};
 
 
 /// Wipes the nanoservices from the Cargo.toml dependencies.
 ///
+/// # Notes
+/// Only the dependencies recorded in the `[package.metadata.nanoforge]` ownership record are removed,
+/// all other dependencies are left untouched. The features that NanoForge created are removed if they end up
+/// empty. A Cargo.toml file prepped before the record existed has no record, so the path dependencies pointing
+/// inside the cache are removed instead. The progress is printed to stderr so it never ends up in the diff of a
+/// dry run.
+///
 /// # Arguments
 /// * `cargo_toml_file` - A mutable reference to the CargoToml struct.
+/// * `cargo_toml_path` - The path to the Cargo.toml file that the dependency paths are relative to.
+/// * `cache_dir` - The absolute path to the cache directory.
 ///
 /// # Returns
 /// None
-pub fn wipe_nanoservices(cargo_toml_file: &mut RawCargoToml) {
-    println!("Wiping nanoservices");
-    for (name, value) in cargo_toml_file.dependencies.clone() {
-        match value {
-            Value::Table(table) => {
-                match table.get("path") {
-                    Some(path) => {
-                        let path = path.as_str().unwrap();
-                        if path.contains(".nanoservices_cache") {
-                            println!("Removing nanoservice: {}", name);
-                            cargo_toml_file.dependencies.remove(&name);
-                        }
-                    },
-                    None => ()
-                }
-            },
-            _ => ()
+pub fn wipe_nanoservices(cargo_toml_file: &mut RawCargoToml, cargo_toml_path: &Path, cache_dir: &Path) {
+    eprintln!("Wiping nanoservices");
+    let mut managed = get_managed_dependencies(cargo_toml_file);
+    if managed.is_empty() {
+        managed = cargo_toml_file.dependencies.iter()
+            .filter(|(_, value)| {
+                value.get("path")
+                    .and_then(|path| path.as_str())
+                    .map(|path| is_cache_path(path, cargo_toml_path, cache_dir))
+                    .unwrap_or(false)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        managed.sort();
+    }
+    for name in managed {
+        if cargo_toml_file.dependencies.remove(&name).is_some() {
+            eprintln!("Removing nanoservice: {}", name);
         }
+        cargo_toml_file.remove_dependency_from_features(&name);
     }
+    cargo_toml_file.remove_empty_features(&get_managed_features(cargo_toml_file));
+    clear_managed_dependencies(cargo_toml_file);
 }
 
 
@@ -35,9 +54,10 @@ pub fn wipe_nanoservices(cargo_toml_file: &mut RawCargoToml) {
 mod tests {
 
     use super::*;
-    use crate::toml_operations::kernel::Package;
-    use std::collections::HashMap;
-    use toml::Table;
+    use crate::project_config::config::NanoforgeConfig;
+    use crate::toml_operations::nanoservices::ownership::{record_managed_dependencies, record_managed_features};
+    use std::collections::{BTreeMap, HashMap};
+    use toml::{Table, Value};
 
     #[test]
     fn test_wipe_nanoservices() {
@@ -56,16 +76,10 @@ mod tests {
         );
         dependencies.insert("test4".to_string(), Value::Table(nanoservice_table));
 
-        let mut cargo_toml = RawCargoToml {
-            package: Package {
-                name: "test".to_string(),
-                version: "0.1.0".to_string(),
-                edition: "2018".to_string()
-            },
-            dependencies,
-            nanoservices: None
-        };
-        wipe_nanoservices(&mut cargo_toml);
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.dependencies = dependencies;
+        record_managed_dependencies(&mut cargo_toml, vec!["test4".to_string()]);
+        wipe_nanoservices(&mut cargo_toml, Path::new("Cargo.toml"), &NanoforgeConfig::default().cache_dir);
 
         assert_eq!(cargo_toml.dependencies.len(), 3);
         assert_eq!(cargo_toml.dependencies.contains_key("test"), true);
@@ -73,4 +87,91 @@ mod tests {
         assert_eq!(cargo_toml.dependencies.contains_key("test3"), true);
     }
 
+    #[test]
+    fn test_wipe_nanoservices_removes_feature_entries() {
+        let mut nanoservice_table = Table::new();
+        nanoservice_table.insert("path".to_string(), Value::String(
+            ".nanoservices_cache/one/two".to_string())
+        );
+        nanoservice_table.insert("optional".to_string(), Value::Boolean(true));
+        let mut dependencies = HashMap::new();
+        dependencies.insert("nan-one".to_string(), Value::Table(nanoservice_table));
+
+        let mut features = BTreeMap::new();
+        features.insert(
+            "with-nan-one".to_string(),
+            vec!["dep:nan-one".to_string(), "serde".to_string()]
+        );
+
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.dependencies = dependencies;
+        cargo_toml.features = Some(features);
+        record_managed_dependencies(&mut cargo_toml, vec!["nan-one".to_string()]);
+        wipe_nanoservices(&mut cargo_toml, Path::new("Cargo.toml"), &NanoforgeConfig::default().cache_dir);
+
+        assert_eq!(cargo_toml.dependencies.len(), 0);
+        assert_eq!(
+            &vec!["serde".to_string()],
+            cargo_toml.features.unwrap().get("with-nan-one").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_wipe_nanoservices_keeps_unmanaged_cache_paths() {
+        let mut user_table = Table::new();
+        user_table.insert("path".to_string(), Value::String(
+            "../vendor/.nanoservices_cache/my-crate".to_string())
+        );
+        let mut dependencies = HashMap::new();
+        dependencies.insert("my-crate".to_string(), Value::Table(user_table));
+
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.dependencies = dependencies;
+        wipe_nanoservices(&mut cargo_toml, Path::new("Cargo.toml"), &NanoforgeConfig::default().cache_dir);
+
+        assert_eq!(cargo_toml.dependencies.contains_key("my-crate"), true);
+        assert_eq!(cargo_toml.package.metadata, None);
+    }
+
+    #[test]
+    fn test_wipe_nanoservices_without_record_removes_cache_paths() {
+        let mut nanoservice_table = Table::new();
+        nanoservice_table.insert("path".to_string(), Value::String(
+            ".nanoservices_cache/domain_services/nanoservices/maxwellflitton_nan-one/.".to_string())
+        );
+        let mut dependencies = HashMap::new();
+        dependencies.insert("nan-one".to_string(), Value::Table(nanoservice_table));
+        dependencies.insert("tokio".to_string(), Value::String("1.0.0".to_string()));
+
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.dependencies = dependencies;
+        wipe_nanoservices(&mut cargo_toml, Path::new("Cargo.toml"), &NanoforgeConfig::default().cache_dir);
+
+        assert_eq!(vec!["tokio"], cargo_toml.dependencies.keys().collect::<Vec<&String>>());
+    }
+
+    #[test]
+    fn test_wipe_nanoservices_removes_created_features() {
+        let mut nanoservice_table = Table::new();
+        nanoservice_table.insert("path".to_string(), Value::String(
+            ".nanoservices_cache/one/two".to_string())
+        );
+        let mut dependencies = HashMap::new();
+        dependencies.insert("nan-one".to_string(), Value::Table(nanoservice_table));
+
+        let mut features = BTreeMap::new();
+        features.insert("with-nan-one".to_string(), vec!["dep:nan-one".to_string()]);
+        features.insert("user-empty".to_string(), Vec::new());
+
+        let mut cargo_toml = RawCargoToml::fixture("test");
+        cargo_toml.dependencies = dependencies;
+        cargo_toml.features = Some(features);
+        record_managed_dependencies(&mut cargo_toml, vec!["nan-one".to_string()]);
+        record_managed_features(&mut cargo_toml, vec!["with-nan-one".to_string()]);
+        wipe_nanoservices(&mut cargo_toml, Path::new("Cargo.toml"), &NanoforgeConfig::default().cache_dir);
+
+        assert_eq!(vec!["user-empty"], cargo_toml.features.unwrap().keys().collect::<Vec<&String>>());
+        assert_eq!(None, cargo_toml.package.metadata);
+    }
+
 }